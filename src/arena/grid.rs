@@ -15,13 +15,25 @@ use rand::Rng;
 
 use crate::{
     assets::{BwTile, SpriteAssets, Tiles},
-    towers::TowerTypes,
+    session::GameSettings,
+    state::{AppState, RenderSet},
+    towers::{traps::TrapTypes, TowerTypes},
 };
 
 use super::{
     path_finding::{PathFindingEvent, Pos},
     ARENA_HEIGHT,
 };
+/// Side of a grid tile in pixels.
+const TILE_SIZE: f32 = 16.0;
+/// Space left around the grid.
+const GRID_PADDING: f32 = 150.0;
+
+/// Tiles along each side of the square grid.
+pub(crate) fn grid_squares() -> u32 {
+    ((ARENA_HEIGHT - GRID_PADDING) / TILE_SIZE).ceil() as u32
+}
+
 /// The grid plugin.
 pub struct GridPlugin;
 
@@ -39,6 +51,8 @@ pub(crate) struct GridResource {
     pub(crate) grid_enemy_end: TilePos,
     pub(crate) grid_entities: Vec<Vec<Entity>>,
     pub(crate) grid_towers: Vec<Vec<Entity>>,
+    /// Traps sit on walkable tiles, path finding ignores this layer.
+    pub(crate) grid_traps: Vec<Vec<Entity>>,
 }
 
 impl GridResource {
@@ -53,7 +67,8 @@ impl GridResource {
             grid_enemy_start: TilePos::new(10, 5),
             grid_enemy_end: TilePos::new(0, 5),
             grid_entities: vec![vec![]; grid_size as usize],
-            grid_towers: vec![vec![]; grid_size as usize],
+            grid_towers: vec![vec![Entity::PLACEHOLDER; grid_size as usize]; grid_size as usize],
+            grid_traps: vec![vec![Entity::PLACEHOLDER; grid_size as usize]; grid_size as usize],
         }
     }
 
@@ -61,6 +76,18 @@ impl GridResource {
         self.grid = vec![vec![false; self.grid_size as usize]; self.grid_size as usize];
     }
 
+    /// Forget every tower and trap, keeping the tiles.
+    pub(crate) fn clear(&mut self) {
+        self.set_grid();
+        for column in self
+            .grid_towers
+            .iter_mut()
+            .chain(self.grid_traps.iter_mut())
+        {
+            column.fill(Entity::PLACEHOLDER);
+        }
+    }
+
     pub(crate) fn set_occupied(&mut self, pos: &TilePos, entity: Entity) {
         self.grid[pos.x as usize][pos.y as usize] = true;
         self.grid_towers[pos.x as usize][pos.y as usize] = entity;
@@ -75,6 +102,34 @@ impl GridResource {
         self.grid[pos.x as usize][pos.y as usize]
     }
 
+    /// Tiles of a `size` by `size` square with `anchor` as its bottom left corner,
+    /// or `None` if it does not fit on the grid.
+    pub(crate) fn footprint(&self, anchor: &TilePos, size: u32) -> Option<Vec<TilePos>> {
+        if anchor.x + size > self.grid_size || anchor.y + size > self.grid_size {
+            return None;
+        }
+        let tiles = (0..size)
+            .flat_map(|x| (0..size).map(move |y| TilePos::new(anchor.x + x, anchor.y + y)))
+            .collect();
+        Some(tiles)
+    }
+
+    pub(crate) fn is_area_occupied(&self, tiles: &[TilePos]) -> bool {
+        tiles.iter().any(|tile| self.is_occupied(tile))
+    }
+
+    pub(crate) fn set_area_occupied(&mut self, tiles: &[TilePos], entity: Entity) {
+        for tile in tiles {
+            self.set_occupied(tile, entity);
+        }
+    }
+
+    pub(crate) fn remove_area_occupied(&mut self, tiles: &[TilePos]) {
+        for tile in tiles {
+            self.remove_occupied(tile);
+        }
+    }
+
     pub(crate) fn get(&self, x: u32, y: u32) -> bool {
         self.grid[x as usize][y as usize]
     }
@@ -88,6 +143,23 @@ impl GridResource {
         }
     }
 
+    pub(crate) fn set_trap(&mut self, pos: &TilePos, entity: Entity) {
+        self.grid_traps[pos.x as usize][pos.y as usize] = entity;
+    }
+
+    pub(crate) fn remove_trap(&mut self, pos: &TilePos) {
+        self.grid_traps[pos.x as usize][pos.y as usize] = Entity::PLACEHOLDER;
+    }
+
+    pub(crate) fn get_trap(&self, pos: TilePos) -> Option<Entity> {
+        let x = self.grid_traps[pos.x as usize][pos.y as usize];
+        if x == Entity::PLACEHOLDER {
+            None
+        } else {
+            Some(x)
+        }
+    }
+
     pub(crate) fn get_grid(&self) -> Vec<Vec<bool>> {
         self.grid.clone()
     }
@@ -147,8 +219,9 @@ impl GridResource {
         self.grid_size as i32
     }
 
-    pub(crate) fn successors(&self, pos: &TilePos) -> Vec<TilePos> {
-        let mut successors = Vec::new();
+    /// The up to four tiles sharing an edge with `pos`.
+    pub(crate) fn neighbours(&self, pos: &TilePos) -> Vec<TilePos> {
+        let mut neighbours = Vec::new();
         let width = self.width();
         let height = self.height();
         let dx = [0, 1, 0, -1];
@@ -159,15 +232,16 @@ impl GridResource {
             if nx < 0 || nx >= height || ny < 0 || ny >= width {
                 continue;
             }
-            let nx = nx as u32;
-            let ny = ny as u32;
-            // Check if the cell is blocked
-            if self.get(nx, ny) {
-                continue;
-            }
-            successors.push(TilePos::new(nx, ny));
+            neighbours.push(TilePos::new(nx as u32, ny as u32));
         }
 
+        neighbours
+    }
+
+    pub(crate) fn successors(&self, pos: &TilePos) -> Vec<TilePos> {
+        let mut successors = self.neighbours(pos);
+        // Check if the cell is blocked
+        successors.retain(|tile| !self.get(tile.x, tile.y));
         successors
     }
 }
@@ -176,33 +250,44 @@ pub(crate) enum GridClickEvent {
     Highlight(Entity, Transform, TilePos),
     UpgradeTower(TowerTypes, TilePos),
     BuildTower(TowerTypes, Transform, TilePos),
+    BuildTrap(TrapTypes, Transform, TilePos),
+    /// Sell the trap on the tile.
+    RemoveTrap(TilePos),
     RemoveTower(TowerTypes, TilePos),
+    ResearchTower(TowerTypes),
+    /// Buy the next upgrade of a branch in the tower's upgrade tree.
+    UpgradeBranch(TilePos, usize),
     DeHighlight(Transform, TilePos),
 }
 
 #[derive(Debug, Deref, Default, Resource)]
 pub(crate) struct HighlightedSpot(pub(crate) Option<(Entity, Transform, TilePos)>);
 
+/// The tile under the pointer, while it is over the grid and not over a window.
+#[derive(Debug, Deref, Default, PartialEq, Resource)]
+pub(crate) struct HoveredSpot(pub(crate) Option<(Transform, TilePos)>);
+
 #[derive(Debug, Default, Resource)]
 pub(crate) struct HighlightedPaths(pub(crate) Vec<Entity>);
 
 impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(HighlightedSpot::default())
+            .insert_resource(HoveredSpot::default())
             .insert_resource(GridResource::default())
             .insert_resource(HighlightedPaths::default())
             .add_event::<GridClickEvent>()
-            .add_systems(Update, highight)
-            .add_systems(Update, dehighlight)
-            .add_systems(PostStartup, setup);
+            .add_systems(Update, highight.in_set(RenderSet))
+            .add_systems(Update, dehighlight.in_set(RenderSet))
+            .add_systems(PostStartup, setup)
+            .add_systems(OnEnter(AppState::GameCreate), reset_grid);
     }
 }
 
 fn setup(mut commands: Commands, mut grid: ResMut<GridResource>, bwtile: Res<Tiles>) {
-    let square_size = 16.0; // Size of each square
-    let padding = 150.0; // Padding around the grid
-    let pad_height = ARENA_HEIGHT - padding;
-    let squares = (pad_height / square_size).ceil() as i32;
+    let square_size = TILE_SIZE;
+    let pad_height = ARENA_HEIGHT - GRID_PADDING;
+    let squares = grid_squares() as i32;
 
     let line_length = square_size * squares as f32; // Length of the lines
 
@@ -251,6 +336,7 @@ fn setup(mut commands: Commands, mut grid: ResMut<GridResource>, bwtile: Res<Til
     let mut tile_storage = TileStorage::empty(size);
     grid.grid_entities = vec![vec![Entity::PLACEHOLDER; size.y as usize]; size.x as usize];
     grid.grid_towers = vec![vec![Entity::PLACEHOLDER; size.y as usize]; size.x as usize];
+    grid.grid_traps = vec![vec![Entity::PLACEHOLDER; size.y as usize]; size.x as usize];
 
     let mut grid_coords = vec![vec![Vec2::ZERO; size.y as usize]; size.x as usize];
     for x in 0..size.x {
@@ -302,6 +388,18 @@ fn setup(mut commands: Commands, mut grid: ResMut<GridResource>, bwtile: Res<Til
 #[derive(Component)]
 pub(crate) struct LastUpdate(f64);
 
+fn reset_grid(
+    mut grid: ResMut<GridResource>,
+    mut highlighted_spot: ResMut<HighlightedSpot>,
+    mut hovered_spot: ResMut<HoveredSpot>,
+    settings: Res<GameSettings>,
+) {
+    grid.clear();
+    (grid.grid_enemy_start, grid.grid_enemy_end) = settings.map.endpoints(grid.grid_size);
+    highlighted_spot.0 = None;
+    hovered_spot.0 = None;
+}
+
 fn highight(
     mut commands: Commands,
     mut grid_click_events: EventReader<GridClickEvent>,
@@ -394,3 +492,26 @@ fn dehighlight(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_footprint_occupies_every_tile() {
+        let mut grid = GridResource::new(4, 16.0, [(0.0, 0.0); 4]);
+        let tower = Entity::from_raw(1);
+
+        assert!(grid.footprint(&TilePos::new(3, 0), 2).is_none());
+        let tiles = grid.footprint(&TilePos::new(1, 1), 2).unwrap();
+        assert_eq!(tiles.len(), 4);
+
+        grid.set_area_occupied(&tiles, tower);
+        assert_eq!(grid.get_tower(TilePos::new(2, 2)), Some(tower));
+        assert!(grid.is_area_occupied(&grid.footprint(&TilePos::new(0, 0), 2).unwrap()));
+
+        grid.remove_area_occupied(&tiles);
+        assert!(!grid.is_area_occupied(&tiles));
+        assert!(tiles.iter().all(|tile| grid.get_tower(*tile).is_none()));
+    }
+}
@@ -76,6 +76,23 @@ pub(crate) fn path_finding(grid: &GridResource, current: TilePos) -> Option<Vec<
     )
 }
 
+/// Path finding as if the `blocked` tiles were already occupied.
+pub(crate) fn path_finding_blocked(
+    grid: &GridResource,
+    current: TilePos,
+    blocked: &[TilePos],
+) -> Option<Vec<TilePos>> {
+    bfs(
+        &current,
+        |p| {
+            let mut successors = grid.successors(p);
+            successors.retain(|tile| !blocked.contains(tile));
+            successors
+        },
+        |p| p == &grid.grid_enemy_end,
+    )
+}
+
 pub(crate) fn path_mob_finding(grid: &GridResource, current: TilePos) -> Option<TilePos> {
     let x = bfs(
         &current,
@@ -8,6 +8,7 @@ use crate::{
 };
 use bevy::{asset, render::render_resource::Texture, utils::HashMap};
 use bevy_ecs_tilemap::prelude::*;
+use enum_iterator::all;
 
 #[derive(Resource)]
 pub(crate) struct SpriteAssets {
@@ -18,6 +19,24 @@ pub(crate) struct SpriteAssets {
     pub(crate) tower_sprites: HashMap<TowerTypes, Handle<Image>>,
     pub(crate) other: HashMap<Enemies, Handle<Image>>,
 }
+impl SpriteAssets {
+    /// Sprites with empty image handles, for runs without an asset server.
+    pub(crate) fn placeholder() -> Self {
+        let mut sprite_assets = SpriteAssets {
+            player: Handle::default(),
+            weapon_sprites: HashMap::default(),
+            enemy_sprites: HashMap::default(),
+            tower_sprites: all::<TowerTypes>()
+                .map(|tower| (tower, Handle::default()))
+                .collect(),
+            other: HashMap::default(),
+        };
+        WeaponTypes::set(&mut sprite_assets);
+        Enemies::set(&mut sprite_assets);
+        sprite_assets
+    }
+}
+
 /// The assets plugin.
 pub struct AssetsPlugin;
 
@@ -77,4 +96,4 @@ pub(crate) struct BwTile(Handle<Image>);
 pub(crate) struct FontHandle(Handle<Font>);
 
 #[derive(Deref, Resource)]
-pub(crate) struct Tiles(Handle<Image>);
+pub(crate) struct Tiles(pub(crate) Handle<Image>);
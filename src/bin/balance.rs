@@ -0,0 +1,317 @@
+//! Balance simulator
+//!
+//! Plays simulated waves against lines of towers and prints how much damage each brick buys.
+//!
+//! ```text
+//! cargo run --release --bin balance -- --towers basic,sniper --counts 1,2,4 --upgrades 0,10 --format json
+//! ```
+
+use std::{env, process::ExitCode, str::FromStr};
+
+use enum_iterator::all;
+use rusty_apple::{
+    mob::{Enemies, Wave},
+    sim::{armed_towers, Scenario, ScenarioTower},
+    towers::TowerTypes,
+};
+
+const USAGE: &str = "Usage: balance [options]
+  --towers <a,b,..>    tower types to test (default: every tower with a weapon)
+  --counts <n,..>      towers per build (default: 1,2,4)
+  --upgrades <n,..>    upgrades bought on each tower (default: 0,5,10)
+  --level <n>          level of the first wave, each next wave is one level up (default: 1)
+  --waves <n>          waves to play, the nth wave has 10n enemies (default: 5)
+  --seed <n>           rng seed (default: 0)
+  --time-limit <secs>  game time before a run gives up (default: 600)
+  --format <csv|json>  output format (default: csv)";
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Debug)]
+struct Options {
+    towers: Vec<TowerTypes>,
+    counts: Vec<usize>,
+    upgrades: Vec<u32>,
+    level: u32,
+    waves: u32,
+    seed: u64,
+    time_limit: f32,
+    format: Format,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            towers: armed_towers(),
+            counts: vec![1, 2, 4],
+            upgrades: vec![0, 5, 10],
+            level: 1,
+            waves: 5,
+            seed: 0,
+            time_limit: 600.0,
+            format: Format::Csv,
+        }
+    }
+}
+
+/// One build of the sweep and how it did.
+struct Row {
+    tower: TowerTypes,
+    count: usize,
+    upgrades: u32,
+    built: usize,
+    spent: u32,
+    damage: u128,
+    kills: usize,
+    leaks: usize,
+    waves_cleared: u32,
+}
+
+impl Row {
+    fn damage_per_brick(&self) -> f64 {
+        if self.spent == 0 {
+            0.0
+        } else {
+            self.damage as f64 / self.spent as f64
+        }
+    }
+}
+
+fn parse<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid value '{}' for {}", value, flag))
+}
+
+fn parse_list<T: FromStr>(flag: &str, value: &str) -> Result<Vec<T>, String> {
+    value.split(',').map(|item| parse(flag, item)).collect()
+}
+
+fn parse_tower(name: &str) -> Result<TowerTypes, String> {
+    all::<TowerTypes>()
+        .find(|tower| tower.to_string().eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| format!("Unknown tower '{}'", name))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--towers" => {
+                options.towers = value
+                    .split(',')
+                    .map(parse_tower)
+                    .collect::<Result<_, _>>()?
+            }
+            "--counts" => options.counts = parse_list(&flag, &value)?,
+            "--upgrades" => options.upgrades = parse_list(&flag, &value)?,
+            "--level" => options.level = parse(&flag, &value)?,
+            "--waves" => options.waves = parse(&flag, &value)?,
+            "--seed" => options.seed = parse(&flag, &value)?,
+            "--time-limit" => options.time_limit = parse(&flag, &value)?,
+            "--format" => {
+                options.format = match value.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => return Err(format!("Unknown format '{}'", value)),
+                }
+            }
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+    Ok(options)
+}
+
+fn of_tower<T: Copy + Default>(list: &[(TowerTypes, T)], tower: TowerTypes) -> T {
+    list.iter()
+        .find(|(built, _)| *built == tower)
+        .map_or(T::default(), |(_, value)| *value)
+}
+
+fn run(options: &Options, tower: TowerTypes, count: usize, upgrades: u32) -> Row {
+    let scenario = Scenario {
+        seed: options.seed,
+        towers: ScenarioTower::along_path(tower, count, upgrades),
+        waves: (0..options.waves)
+            .map(|wave| Wave {
+                enemy: Enemies::Block,
+                level: options.level + wave,
+                count: (wave as usize + 1) * 10,
+            })
+            .collect(),
+        time_limit: options.time_limit,
+    };
+    let summary = scenario.run();
+    Row {
+        tower,
+        count,
+        upgrades,
+        built: summary.towers_built,
+        spent: of_tower(&summary.spent, tower),
+        damage: of_tower(&summary.damage, tower),
+        kills: summary.kills,
+        leaks: summary.leaks,
+        waves_cleared: summary.waves_cleared,
+    }
+}
+
+/// Quotes a CSV field holding a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A JSON string literal with quotes, backslashes and control characters escaped.
+fn json_string(value: &str) -> String {
+    let mut literal = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn to_csv(rows: &[Row]) -> String {
+    let mut csv = String::from(
+        "tower,count,upgrades,built,spent,damage,kills,leaks,waves_cleared,damage_per_brick\n",
+    );
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{:.3}\n",
+            csv_field(&row.tower.to_string()),
+            row.count,
+            row.upgrades,
+            row.built,
+            row.spent,
+            row.damage,
+            row.kills,
+            row.leaks,
+            row.waves_cleared,
+            row.damage_per_brick()
+        ));
+    }
+    csv
+}
+
+fn to_json(rows: &[Row]) -> String {
+    let objects = rows
+        .iter()
+        .map(|row| {
+            format!(
+                "  {{\"tower\": {}, \"count\": {}, \"upgrades\": {}, \"built\": {}, \"spent\": {}, \
+                 \"damage\": {}, \"kills\": {}, \"leaks\": {}, \"waves_cleared\": {}, \"damage_per_brick\": {:.3}}}",
+                json_string(&row.tower.to_string()),
+                row.count,
+                row.upgrades,
+                row.built,
+                row.spent,
+                row.damage,
+                row.kills,
+                row.leaks,
+                row.waves_cleared,
+                row.damage_per_brick()
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut rows = Vec::new();
+    for &tower in options.towers.iter() {
+        for &count in options.counts.iter() {
+            for &upgrades in options.upgrades.iter() {
+                rows.push(run(&options, tower, count, upgrades));
+            }
+        }
+    }
+
+    match options.format {
+        Format::Csv => print!("{}", to_csv(&rows)),
+        Format::Json => print!("{}", to_json(&rows)),
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args(
+            "--towers basic,Sniper --counts 1,3 --upgrades 0 --seed 7 --format json",
+        ))
+        .unwrap();
+        assert_eq!(options.towers, vec![TowerTypes::Basic, TowerTypes::Sniper]);
+        assert_eq!(options.counts, vec![1, 3]);
+        assert_eq!(options.upgrades, vec![0]);
+        assert_eq!(options.seed, 7);
+        assert_eq!(options.format, Format::Json);
+        // Anything not given keeps its default
+        assert_eq!(options.waves, 5);
+        assert_eq!(parse_args(args("")).unwrap().format, Format::Csv);
+
+        let error = |line| parse_args(args(line)).unwrap_err();
+        assert_eq!(error("--speed 2"), "Unknown option --speed");
+        assert_eq!(error("--waves"), "Missing value for --waves");
+        assert_eq!(error("--counts 1,two"), "Invalid value 'two' for --counts");
+        assert_eq!(error("--towers basic,cannon"), "Unknown tower 'cannon'");
+        assert_eq!(error("--format xml"), "Unknown format 'xml'");
+    }
+
+    #[test]
+    fn test_output_escaping() {
+        assert_eq!(csv_field("Basic"), "Basic");
+        assert_eq!(csv_field("Big, \"Bad\""), "\"Big, \"\"Bad\"\"\"");
+        assert_eq!(json_string("Basic"), "\"Basic\"");
+        assert_eq!(json_string("Big \"Bad\"\\\n"), "\"Big \\\"Bad\\\"\\\\\\n\"");
+
+        let row = Row {
+            tower: TowerTypes::Basic,
+            count: 2,
+            upgrades: 5,
+            built: 2,
+            spent: 40,
+            damage: 100,
+            kills: 9,
+            leaks: 1,
+            waves_cleared: 1,
+        };
+        assert_eq!(
+            to_csv(&[row]).lines().nth(1),
+            Some("Basic,2,5,2,40,100,9,1,1,2.500")
+        );
+    }
+}
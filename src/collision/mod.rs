@@ -12,16 +12,23 @@ use bevy::{
 };
 use bevy_ecs_tilemap::tiles::TilePos;
 use bevy_egui::egui::debug_text::print;
-use rand::{thread_rng, Rng};
 
 use crate::{
+    arena::grid::GridResource,
     mob::{
         enemy::{self, ScheduledForDespawnEnemy},
         EffectType, EnemyComponent, EnemyUnit, MobDespawnEvent,
     },
     prelude::*,
-    towers::{TowerComponents, TowerData, TowerInfo, TowerLevelUp, TowerLevelUpReason, TowerTypes},
-    weapons::{weapon::ProjectileData, DespawnProjectileEvent, ScheduledForDespawnProjectile},
+    state::GameplaySet,
+    towers::{
+        synergy::TowerSynergies, TowerComponents, TowerInfo, TowerLevelUp, TowerLevelUpReason,
+        TowerProgress, TowerTypes,
+    },
+    weapons::{
+        weapon::{ProjectileData, ProjectileHits},
+        DespawnProjectileEvent, ScheduledForDespawnProjectile, SplashEvent,
+    },
 };
 
 #[derive(PhysicsLayer)]
@@ -49,8 +56,14 @@ pub struct CollisionPlugin;
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<CollisionTypes>()
-            .add_systems(Update, collision_events_types_system)
-            .add_systems(Update, read_projectile_to_enemy_collision_event);
+            .add_systems(
+                FixedUpdate,
+                collision_events_types_system.in_set(GameplaySet),
+            )
+            .add_systems(
+                FixedUpdate,
+                read_projectile_to_enemy_collision_event.in_set(GameplaySet),
+            );
     }
 }
 
@@ -90,13 +103,17 @@ fn collision_events_types_system(
 fn read_projectile_to_enemy_collision_event(
     mut enemies: Query<(Entity, &mut EnemyUnit, &mut Sprite, &Position, &TilePos)>,
     mut collision_events: EventReader<CollisionTypes>,
+    mut projectile_hits: Query<&mut ProjectileHits>,
     mut enemy_despawn_events: EventWriter<MobDespawnEvent>,
     mut projectile_despawn_events: EventWriter<DespawnProjectileEvent>,
     mut projectile_despawn_schedule: ResMut<ScheduledForDespawnProjectile>,
     mut enemy_despawn_schedule: ResMut<ScheduledForDespawnEnemy>,
     mut tower_level_up_events: EventWriter<TowerLevelUp>,
-    tower_components: Query<&TowerComponents>,
-    tower_info: Res<TowerInfo>,
+    mut splash_events: EventWriter<SplashEvent>,
+    mut tower_components: Query<(&TowerComponents, &mut TowerProgress, &TowerSynergies)>,
+    mut tower_info: ResMut<TowerInfo>,
+    spatial_query: SpatialQuery,
+    grid: Res<GridResource>,
 ) {
     for event in collision_events.read() {
         match event {
@@ -104,7 +121,7 @@ fn read_projectile_to_enemy_collision_event(
                 mob_entity,
                 projectile_entity,
                 projectile_data,
-                tile,
+                ..
             } => {
                 // We want to avoid the projectile doing damage to the same enemy multiple times
                 // Or penetrating the enemy when we dont want it to
@@ -114,29 +131,86 @@ fn read_projectile_to_enemy_collision_event(
                     continue;
                 }
 
+                // A piercing projectile never damages the same enemy twice
+                let hit_count = match projectile_hits.get_mut(*projectile_entity) {
+                    Ok(mut hits) => {
+                        if !hits.insert(*mob_entity) {
+                            continue;
+                        }
+                        hits.len()
+                    }
+                    Err(_) => 1,
+                };
+
                 // The the shedule it too big clean it up
                 if projectile_despawn_schedule.len() > 99_000 {
                     projectile_despawn_schedule.clear();
                 }
 
-                fn tile_check(proj_tile: &TilePos, enemy_tile: &TilePos, _potency: u32) -> bool {
-                    if proj_tile == enemy_tile {
-                        return true;
+                // Find every enemy inside the splash radius around the struck enemy
+                let mut targets = vec![(*mob_entity, projectile_data.damage)];
+                if let (true, Ok((_, _, _, centre, _))) =
+                    (projectile_data.area_of_effect(), enemies.get(*mob_entity))
+                {
+                    let centre = centre.0;
+                    let range = projectile_data.splash_range(&grid);
+                    let splashed = spatial_query.shape_intersections(
+                        &Collider::circle(range),
+                        centre,
+                        0.0,
+                        SpatialQueryFilter::from_mask(GameLayer::Enemy),
+                    );
+                    for entity in splashed {
+                        if entity == *mob_entity {
+                            continue;
+                        }
+                        if let Ok((_, _, _, position, _)) = enemies.get(entity) {
+                            let distance = centre.distance(position.0);
+                            targets.push((entity, projectile_data.splash_damage(distance, range)));
+                        }
                     }
-                    false
+                    splash_events.send(SplashEvent {
+                        position: centre,
+                        radius: range,
+                    });
                 }
 
-                for (entity, mut unit, _sprite, _position, enemy_tile) in enemies.iter_mut() {
-                    if unit.health <= 0 {
-                        if enemy_despawn_schedule.contains(&entity) {
-                            continue;
+                for (target, damage) in targets {
+                    let Ok((entity, mut unit, _sprite, _position, _tile)) = enemies.get_mut(target)
+                    else {
+                        continue;
+                    };
+                    if enemy_despawn_schedule.contains(&entity) {
+                        continue;
+                    }
+
+                    let source = projectile_data
+                        .source_entity
+                        .and_then(|tower_entity| tower_components.get_mut(tower_entity).ok());
+                    match source {
+                        Some((tower, mut progress, synergies)) => {
+                            // Synergies look at the enemy before this hit's status lands
+                            let damage = synergies.damage(damage, &unit);
+                            // Credit what the hit took off after armor, not what was rolled
+                            let dealt = unit.take_damage(damage);
+                            tower_info.add_damage(&tower.tower, dealt as u32);
+                            progress.add_damage(dealt as u32);
+                            let tower_status = tower_info.status_effects(&tower.tower, &progress);
+                            let status = projectile_data.status_effect(&tower_status);
+                            unit.insert_status(status);
+                        }
+                        None => {
+                            unit.take_damage(damage);
                         }
+                    }
+
+                    if unit.health == 0 {
                         enemy_despawn_events.send(MobDespawnEvent {
                             enemy_entity: entity,
                             spawner_id: unit.spwawner_id,
                             reason: crate::mob::EnemyDespawnReason::Killed,
                         });
-                        enemy_despawn_schedule.insert(*&entity);
+                        enemy_despawn_schedule.insert(entity);
 
                         if let Some(tower_entity) = projectile_data.source_entity {
                             tower_level_up_events.send(TowerLevelUp {
@@ -145,21 +219,13 @@ fn read_projectile_to_enemy_collision_event(
                                 enemy_experience: unit.experience,
                             });
                         }
-                    } else if &entity == mob_entity
-                        || (projectile_data.area_of_effect && tile_check(tile, &enemy_tile, 1))
-                    {
-                        unit.health = unit.health.saturating_sub(projectile_data.damage);
-                        if let Some(tower_entity) = projectile_data.source_entity {
-                            if let Some(tower) = tower_components.get(tower_entity).ok() {
-                                let tower_data = tower_info.get_data(&tower.tower);
-                                let status = projectile_data.status_effect(&tower_data);
-                                unit.insert_status(status);
-                            }
-                        }
                     }
                 }
 
-                if !projectile_despawn_schedule.contains(projectile_entity) {
+                // Let the projectile pass through until it runs out of pierce
+                if hit_count > projectile_data.pierce
+                    && !projectile_despawn_schedule.contains(projectile_entity)
+                {
                     projectile_despawn_schedule.insert(*projectile_entity);
                     projectile_despawn_events.send(DespawnProjectileEvent {
                         projectile_entity: *projectile_entity,
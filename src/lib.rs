@@ -12,6 +12,13 @@ pub mod assets;
 pub mod collision;
 pub mod mob;
 pub mod player;
+pub mod replay;
+pub(crate) mod rng;
+pub mod save;
+pub mod scores;
+pub mod session;
+pub mod sim;
+pub mod state;
 pub mod towers;
 pub mod ui;
 pub mod weapons;
@@ -7,19 +7,27 @@ use bevy::{
 };
 use bevy_ecs_tilemap::TilemapPlugin;
 use bevy_egui::EguiPlugin;
-use bevy_xpbd_2d::prelude::PhysicsPlugins;
+use bevy_xpbd_2d::prelude::{Physics, PhysicsPlugins};
 use rusty_apple::{
     arena::{self, PathFindingPlugin, ARENA_HEIGHT, ARENA_WIDTH},
     assets, collision,
     mob::{self, MobPlugin},
-    player, towers, ui,
+    player,
+    replay::ReplayPlugin,
+    save::SavePlugin,
+    scores::ScoresPlugin,
+    session::SessionPlugin,
+    state::{StatesPlugin, TIMESTEP_HZ},
+    towers, ui,
     weapons::WeaponPlugin,
 };
 
 fn main() {
     App::new()
         .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-        .add_plugins(PhysicsPlugins::default())
+        // One physics step per gameplay step
+        .add_plugins(PhysicsPlugins::new(FixedUpdate))
+        .insert_resource(Time::new_with(Physics::fixed_once_hz(TIMESTEP_HZ)))
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Rusty-Apple".to_string(),
@@ -42,6 +50,12 @@ fn main() {
         .add_plugins(ui::InputPlugin)
         .add_plugins(player::PlayerPlugin)
         .add_plugins(ui::StatsPlugin)
+        .add_plugins(StatesPlugin)
+        .add_plugins(SessionPlugin)
+        .add_plugins(SavePlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(ScoresPlugin)
+        .add_plugins(ui::MenuPlugin)
         .add_systems(Startup, setup_camera)
         .run();
 }
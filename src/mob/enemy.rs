@@ -3,6 +3,7 @@
 use std::collections::HashSet;
 
 use bevy_ecs_tilemap::tiles::TilePos;
+use rand::Rng;
 
 use crate::{
     arena::{
@@ -13,12 +14,16 @@ use crate::{
     collision::GameLayer,
     player::PlayerUpdateEvent,
     prelude::*,
-    ui::level::{LevelMap, MapLevel},
+    rng::GameRng,
+    state::{AppState, GameplaySet, RenderSet, StateDespawnMarker, IN_GAME},
+    towers::traps::TrapTriggerEvent,
+    ui::level::{level_up_map_system, LevelMap, MapLevel},
 };
 
 use super::{
-    EffectType, Enemies, EnemyComponent, EnemyDespawnReason, EnemyUnit, MobDespawnEvent,
-    MobSpawnEvent, MobSpawner, SpawnId,
+    status::{draw_status_indicator_system, status_effect_system},
+    Camouflage, Enemies, EnemyComponent, EnemyDespawnReason, EnemyUnit, MobDespawnEvent,
+    MobSpawnEvent, MobSpawner, SpawnIds, Wave, WaveScript,
 };
 
 /// The mob plugin.
@@ -29,18 +34,39 @@ impl Plugin for MobPlugin {
         app.add_event::<MobSpawnEvent>()
             .add_event::<MobDespawnEvent>()
             .insert_resource(ScheduledForDespawnEnemy::default())
-            .add_systems(Update, (deploy_mod_spawners, despawn_mob_spawners).chain())
-            .add_systems(Update, level_up_on_kills_reached)
-            .add_systems(Update, spawn_enemy)
-            .add_systems(Update, mob_spawn_system)
-            .add_systems(Update, follow_path)
+            .init_resource::<SpawnIds>()
+            // A cleared wave levels the map up before the next spawner reads the level
             .add_systems(
-                Update,
-                (trigger_move_to_start_position, mob_despawn_system).chain(),
+                FixedUpdate,
+                (
+                    level_up_on_kills_reached.before(level_up_map_system),
+                    (despawn_mob_spawners, deploy_mod_spawners)
+                        .chain()
+                        .after(level_up_map_system),
+                )
+                    .in_set(GameplaySet),
+            )
+            .add_systems(FixedUpdate, spawn_enemy.in_set(GameplaySet))
+            .add_systems(FixedUpdate, mob_spawn_system.in_set(GameplaySet))
+            .add_systems(FixedUpdate, follow_path.in_set(GameplaySet))
+            .add_systems(FixedUpdate, status_effect_system.in_set(GameplaySet))
+            .add_systems(Update, draw_status_indicator_system.in_set(RenderSet))
+            .add_systems(Update, camouflage_visual_system.in_set(RenderSet))
+            .add_systems(OnEnter(AppState::GameCreate), reset_enemies)
+            .add_systems(
+                FixedUpdate,
+                (trigger_move_to_start_position, mob_despawn_system)
+                    .chain()
+                    .in_set(GameplaySet),
             );
     }
 }
 
+fn reset_enemies(mut commands: Commands, mut enemy_schedule: ResMut<ScheduledForDespawnEnemy>) {
+    enemy_schedule.clear();
+    commands.insert_resource(SpawnIds::default());
+}
+
 fn level_up_on_kills_reached(
     mob_query: Query<&EnemyComponent>,
     mut level_up_event_writer: EventWriter<LevelMap>,
@@ -59,21 +85,26 @@ fn level_up_on_kills_reached(
 fn mob_spawn_system(
     mut event: EventWriter<MobSpawnEvent>,
     mut mob_query: Query<&mut EnemyComponent>,
-    map_level: Res<MapLevel>,
     time: Res<Time>,
 ) {
     for mut enemy in mob_query.iter_mut() {
-        if enemy.spawner.timer.tick(time.delta()).just_finished()
-            && enemy.spawner.current_count < enemy.spawner.max_count
-        {
+        // A long step can cover several spawn periods
+        let due = enemy
+            .spawner
+            .timer
+            .tick(time.delta())
+            .times_finished_this_tick();
+        for _ in 0..due {
+            if enemy.spawner.current_count >= enemy.spawner.max_count {
+                break;
+            }
             event.send(MobSpawnEvent {
                 mob_type: enemy.mob_type,
                 position: enemy.spawner.spawn_position,
                 spawner_id: enemy.spawner.spawner_id,
-                map_level: map_level.level,
+                map_level: enemy.spawner.level,
             });
             enemy.spawner.current_count += 1;
-            enemy.spawner.timer.reset();
         }
     }
 }
@@ -83,15 +114,27 @@ fn deploy_mod_spawners(
     ec: Query<&mut EnemyComponent>,
     map_level: Res<MapLevel>,
     grid: Res<GridResource>,
+    mut spawn_ids: ResMut<SpawnIds>,
+    wave_script: Option<Res<WaveScript>>,
 ) {
     let ec_count = ec.iter().count();
     if ec_count == 0 {
-        let spawn_rate = (5.0 / map_level.level as f32).min(0.15);
+        let wave = match wave_script {
+            Some(script) => match script.0.get(map_level.level.saturating_sub(1) as usize) {
+                Some(wave) => *wave,
+                None => return,
+            },
+            None => Wave {
+                enemy: Enemies::Block,
+                level: map_level.level,
+                count: (map_level.level as f32 * 10.0).ceil() as usize,
+            },
+        };
+        let spawn_rate = (5.0 / wave.level as f32).min(0.15);
         let timer = Timer::from_seconds(spawn_rate, TimerMode::Repeating);
-        let max_count = (map_level.level as f32 * 2.0).ceil() as usize;
-        let max_kill = (map_level.level as f32 * 10.0).ceil() as usize;
-        // Spawn a block enemy
-        let enemy = Enemies::Block;
+        let max_count = (wave.level as f32 * 2.0).ceil() as usize;
+        let max_kill = wave.count;
+        let enemy = wave.enemy;
         let postion = to_position(grid.grid_enemy_start, &grid);
         let component = EnemyComponent {
             mob_type: enemy,
@@ -103,11 +146,12 @@ fn deploy_mod_spawners(
                 current_count: 0,
                 max_kill,
                 current_kill: 0,
-                spawner_id: SpawnId::new(),
+                spawner_id: spawn_ids.next(),
+                level: wave.level,
             },
         };
         let mut entity = commands.spawn_empty();
-        entity.insert(component);
+        entity.insert((component, StateDespawnMarker(IN_GAME)));
     }
 }
 
@@ -131,6 +175,7 @@ fn spawn_enemy(
     assets: Res<SpriteAssets>,
     mut event: EventReader<MobSpawnEvent>,
     grid: Res<GridResource>,
+    mut rng: ResMut<GameRng>,
 ) {
     for mob_spawn_event in event.read() {
         let sprite = assets.enemy_sprites[&mob_spawn_event.mob_type].clone();
@@ -140,7 +185,7 @@ fn spawn_enemy(
             .into_unit(mob_spawn_event.spawner_id, mob_spawn_event.map_level);
         let init_transform = to_position(grid.grid_enemy_start, &grid);
 
-        commands.spawn((
+        let mut enemy = commands.spawn((
             SpriteBundle {
                 sprite,
                 transform: Transform {
@@ -159,7 +204,20 @@ fn spawn_enemy(
             CollisionLayers::new(GameLayer::Enemy, [GameLayer::Projectile, GameLayer::Tower]),
             enemy_unit,
             grid.grid_enemy_start,
+            StateDespawnMarker(IN_GAME),
         ));
+
+        // From level 5 some enemies arrive camouflaged
+        if mob_spawn_event.map_level >= 5 && rng.gen_bool(0.1) {
+            enemy.insert(Camouflage::default());
+        }
+    }
+}
+
+fn camouflage_visual_system(mut query: Query<(&Camouflage, &mut Sprite), Changed<Camouflage>>) {
+    for (camouflage, mut sprite) in query.iter_mut() {
+        let alpha = if camouflage.revealed { 1.0 } else { 0.25 };
+        sprite.color.set_a(alpha);
     }
 }
 
@@ -223,67 +281,64 @@ fn follow_path(
     mut query: Query<(Entity, &mut Transform, &mut EnemyUnit, &mut TilePos), With<EnemyUnit>>,
     mut mob_despawn_event: EventWriter<MobDespawnEvent>,
     mut enemy_schedule: ResMut<ScheduledForDespawnEnemy>,
+    mut trap_events: EventWriter<TrapTriggerEvent>,
 ) {
     for (entity, mut transform, mut enemy_unit, mut tile_pos) in query.iter_mut() {
-        if enemy_unit
-            .next_position
-            .map_or(true, |next| transform.translation.distance(next) < 0.1)
-        {
-            let current = from_transform(&transform, grid.grid_square_size, grid.bottom_left());
-            let path = path_mob_finding(&grid, current);
-            if let Some(next_pos) = path {
-                enemy_unit.next_position = Some(to_transform(next_pos, &grid).translation);
-                *tile_pos = next_pos;
-            } else {
-                // If the enemy unit has reached the end of the path, despawn it
-                if enemy_schedule.contains(&entity) {
-                    continue;
-                }
-                mob_despawn_event.send(MobDespawnEvent {
-                    enemy_entity: entity,
-                    spawner_id: enemy_unit.spwawner_id,
-                    reason: EnemyDespawnReason::ReachedEnd,
-                });
-                enemy_schedule.insert(entity);
-                continue;
-            }
-        }
-
-        // Move the enemy unit towards the next position
-        if let Some(next_position) = enemy_unit.next_position {
-            let mut speed = enemy_unit.move_speed;
-            if let Some(slow) = enemy_unit.status_effects.get_mut(&EffectType::Slow) {
-                slow.timer.tick(time.delta());
-                if slow.timer.just_finished() {
-                    enemy_unit.status_effects.remove(&EffectType::Slow);
-                } else {
-                    let slow_effect = (100u32.saturating_sub(slow.potency)).max(1) as f32 / 100.0;
-                    speed *= slow_effect;
-                }
-            }
-
-            let distance_to_target = transform.translation.distance(next_position);
-            let distance_to_move = speed * time.delta_seconds();
+        let speed = enemy_unit.move_speed * enemy_unit.speed_multiplier();
+        let mut distance_to_move = speed * time.delta_seconds();
 
-            if distance_to_target < distance_to_move {
-                // If the entity would move past the target position, check for the next target position
+        // Walk tile by tile so a long step can't cut corners off the path
+        for _ in 0..MAX_TILES_PER_FRAME {
+            if enemy_unit
+                .next_position
+                .map_or(true, |next| transform.translation.distance(next) < 0.1)
+            {
                 let current = from_transform(&transform, grid.grid_square_size, grid.bottom_left());
                 let path = path_mob_finding(&grid, current);
                 if let Some(next_pos) = path {
-                    // If the next target position is available, start moving towards it
                     enemy_unit.next_position = Some(to_transform(next_pos, &grid).translation);
-                    let direction = (next_position - transform.translation).normalize();
-                    transform.translation += direction * distance_to_move;
+                    *tile_pos = next_pos;
+                    if let Some(trap) = grid.get_trap(next_pos) {
+                        trap_events.send(TrapTriggerEvent {
+                            trap,
+                            enemy: entity,
+                        });
+                    }
                 } else {
-                    // If the next target position is not available, set the entity's position to the current target position directly
-                    transform.translation = next_position;
+                    // If the enemy unit has reached the end of the path, despawn it
+                    if !enemy_schedule.contains(&entity) {
+                        mob_despawn_event.send(MobDespawnEvent {
+                            enemy_entity: entity,
+                            spawner_id: enemy_unit.spwawner_id,
+                            reason: EnemyDespawnReason::ReachedEnd,
+                        });
+                        enemy_schedule.insert(entity);
+                    }
+                    break;
                 }
-            } else {
+            }
+
+            // Move the enemy unit towards the next position
+            let Some(next_position) = enemy_unit.next_position else {
+                break;
+            };
+            let distance_to_target = transform.translation.distance(next_position);
+            if distance_to_target > distance_to_move {
                 let direction = (next_position - transform.translation).normalize();
                 transform.translation += direction * distance_to_move;
+                break;
+            }
+            // Reached the tile, carry the rest of the move on to the next one
+            transform.translation = next_position;
+            distance_to_move -= distance_to_target;
+            if distance_to_move <= 0.0 {
+                break;
             }
         }
     }
 }
+/// Tiles an enemy may cross in one step.
+const MAX_TILES_PER_FRAME: usize = 8;
+
 #[derive(Debug, Resource, Deref, Default, DerefMut)]
 pub(crate) struct ScheduledForDespawnEnemy(pub(crate) HashSet<Entity>);
@@ -1,18 +1,21 @@
 //! Mobs
 
-use std::{collections::HashMap, hash::Hash, sync::atomic::AtomicUsize};
+use std::collections::BTreeMap;
 
 use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
 
-use crate::{assets::SpriteAssets, prelude::*};
-
-static SPAWNER_ID: AtomicUsize = AtomicUsize::new(0);
+use crate::{assets::SpriteAssets, prelude::*, save::SavedSpawner};
 
 pub(crate) mod enemy;
+pub(crate) mod status;
 pub use enemy::MobPlugin;
+pub(crate) use status::{EffectType, StatusEffect};
 
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Sequence)]
-pub(crate) enum Enemies {
+/// Enemy kinds.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Sequence, Serialize, Deserialize)]
+pub enum Enemies {
+    /// A plain square.
     Block,
 }
 
@@ -32,34 +35,18 @@ fn block_enemy_sprite() -> Sprite {
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct StatusEffect {
-    pub(crate) effect_type: EffectType,
-    pub(crate) timer: Timer,
-    pub(crate) potency: u32,
+/// Hidden enemies can only be targeted while a radar tower reveals them.
+#[derive(Debug, Component, Clone, Copy, Default)]
+pub(crate) struct Camouflage {
+    pub(crate) revealed: bool,
 }
 
-impl Hash for StatusEffect {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.effect_type.hash(state);
+impl Camouflage {
+    pub(crate) fn targetable(camouflage: Option<&Camouflage>) -> bool {
+        camouflage.map_or(true, |camouflage| camouflage.revealed)
     }
 }
 
-impl PartialEq for StatusEffect {
-    fn eq(&self, other: &Self) -> bool {
-        self.effect_type == other.effect_type
-    }
-}
-
-impl Eq for StatusEffect {}
-
-#[derive(Debug, Clone, PartialEq, Hash, Eq, Copy)]
-pub(crate) enum EffectType {
-    None,
-    Slow,
-    // add other effect types here
-}
-
 /// The enemy component.
 #[derive(Component, Debug)]
 pub(crate) struct EnemyComponent {
@@ -76,7 +63,9 @@ pub(crate) struct EnemyUnit {
     pub(crate) move_speed: f32,
     pub(crate) experience: usize,
     pub(crate) bricks: usize,
-    pub(crate) status_effects: HashMap<EffectType, StatusEffect>,
+    pub(crate) armor: usize,
+    /// Ordered by effect type, so effects tick in the same order in every run.
+    pub(crate) status_effects: BTreeMap<EffectType, StatusEffect>,
 }
 
 impl EnemyUnit {
@@ -84,7 +73,58 @@ impl EnemyUnit {
         if effect.effect_type == EffectType::None {
             return;
         }
-        self.status_effects.insert(effect.effect_type, effect);
+        match self.status_effects.get_mut(&effect.effect_type) {
+            Some(existing) => existing.stack(effect),
+            None => {
+                self.status_effects.insert(effect.effect_type, effect);
+            }
+        }
+    }
+
+    pub(crate) fn has_status(&self, effect_type: EffectType) -> bool {
+        self.status_effects.contains_key(&effect_type)
+    }
+
+    pub(crate) fn speed_multiplier(&self) -> f32 {
+        if self
+            .status_effects
+            .keys()
+            .any(|effect| effect.immobilizes())
+        {
+            return 0.0;
+        }
+        self.status_effects
+            .get(&EffectType::Slow)
+            .map_or(1.0, |slow| {
+                (100u32.saturating_sub(slow.intensity())).max(1) as f32 / 100.0
+            })
+    }
+
+    /// Armor left after any armor shred.
+    pub(crate) fn armor(&self) -> usize {
+        let shred = self
+            .status_effects
+            .get(&EffectType::ArmorShred)
+            .map_or(0, |shred| shred.intensity() as usize);
+        self.armor.saturating_sub(shred)
+    }
+
+    /// Damage the unit would take from a hit after armor and vulnerability.
+    pub(crate) fn damage_taken(&self, damage: usize) -> usize {
+        let vulnerability = self
+            .status_effects
+            .get(&EffectType::Vulnerability)
+            .map_or(1.0, |vulnerability| {
+                1.0 + vulnerability.intensity() as f32 / 100.0
+            });
+        (damage.saturating_sub(self.armor()).max(1) as f32 * vulnerability) as usize
+    }
+
+    /// Apply a hit and return the damage actually dealt.
+    pub(crate) fn take_damage(&mut self, damage: usize) -> usize {
+        let dealt = self.damage_taken(damage).min(self.health);
+        self.health -= dealt;
+        dealt
     }
 }
 
@@ -106,10 +146,27 @@ pub(crate) struct MobSpawner {
     pub(crate) max_kill: usize,
     pub(crate) current_kill: usize,
     spawner_id: SpawnId,
+    /// Level the spawned enemies are scaled to.
+    level: u32,
+}
+
+/// A wave of enemies to clear before the next one is sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wave {
+    /// The kind of enemy.
+    pub enemy: Enemies,
+    /// Level the enemies are scaled to.
+    pub level: u32,
+    /// Kills needed to clear the wave.
+    pub count: usize,
 }
 
-impl From<MobSpawnerData> for MobSpawner {
-    fn from(data: MobSpawnerData) -> Self {
+/// Waves played in order in place of the endless level scaling, no enemies come after the last.
+#[derive(Debug, Clone, Default, Resource)]
+pub(crate) struct WaveScript(pub(crate) Vec<Wave>);
+
+impl MobSpawner {
+    pub(crate) fn from_data(data: MobSpawnerData, spawner_id: SpawnId) -> Self {
         MobSpawner {
             mob_type: data.mob_type,
             spawn_position: data.spawn_position,
@@ -118,7 +175,35 @@ impl From<MobSpawnerData> for MobSpawner {
             current_count: 0,
             max_kill: 0,
             current_kill: 0,
-            spawner_id: SpawnId::new(),
+            spawner_id,
+            level: 1,
+        }
+    }
+
+    pub(crate) fn saved(&self) -> SavedSpawner {
+        SavedSpawner {
+            enemy: self.mob_type,
+            period: self.timer.duration().as_secs_f32(),
+            max_count: self.max_count,
+            max_kill: self.max_kill,
+            kills: self.current_kill,
+            id: self.spawner_id.id,
+            level: self.level,
+        }
+    }
+
+    /// Enemies on the field are not saved, the spawner sends new ones until the kill count is reached.
+    pub(crate) fn restore(saved: &SavedSpawner, spawn_position: Position) -> Self {
+        MobSpawner {
+            mob_type: saved.enemy,
+            spawn_position,
+            timer: Timer::from_seconds(saved.period, TimerMode::Repeating),
+            max_count: saved.max_count,
+            current_count: 0,
+            max_kill: saved.max_kill,
+            current_kill: saved.kills,
+            spawner_id: SpawnId { id: saved.id },
+            level: saved.level,
         }
     }
 }
@@ -128,11 +213,15 @@ pub(crate) struct SpawnId {
     pub(crate) id: usize,
 }
 
-impl SpawnId {
-    pub(crate) fn new() -> Self {
-        SpawnId {
-            id: SPAWNER_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
-        }
+/// Hands out spawner ids, counted per world so a new game numbers them the same way.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct SpawnIds(pub(crate) usize);
+
+impl SpawnIds {
+    pub(crate) fn next(&mut self) -> SpawnId {
+        let id = SpawnId { id: self.0 };
+        self.0 += 1;
+        id
     }
 }
 
@@ -166,13 +255,15 @@ impl Enemies {
             move_speed: 30.0 + (map_level as f32 * 0.03).min(190.0),
             experience: 1,
             bricks: 1,
-            status_effects: HashMap::new(),
+            armor: 0,
+            status_effects: BTreeMap::new(),
         };
 
         match self {
             Enemies::Block => {
                 base.health = 3 * (map_level.pow(2) as usize);
                 base.experience += map_level as usize / 3;
+                base.armor = map_level as usize / 10;
             }
         }
 
@@ -0,0 +1,299 @@
+//! Status effects
+
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    prelude::*,
+    towers::{TowerComponents, TowerInfo, TowerLevelUp, TowerLevelUpReason, TowerProgress},
+};
+
+use super::{enemy::ScheduledForDespawnEnemy, EnemyDespawnReason, EnemyUnit, MobDespawnEvent};
+
+/// How a new application of an effect combines with one already on the unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StackingPolicy {
+    /// The new application replaces the old one.
+    Refresh,
+    /// Every application adds a stack, up to the limit, and resets the duration.
+    StackIntensity(u32),
+    /// The most potent application wins and the longest duration is kept.
+    KeepStrongest,
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord, Copy, Serialize, Deserialize)]
+pub(crate) enum EffectType {
+    None,
+    Slow,
+    Burn,
+    Stun,
+    Freeze,
+    Poison,
+    Vulnerability,
+    ArmorShred,
+    // add other effect types here
+}
+
+impl EffectType {
+    /// Seconds the effect lasts before any bonus duration from the tower.
+    pub(crate) fn base_duration(&self) -> f32 {
+        match self {
+            EffectType::None => 0.0,
+            EffectType::Slow => 0.0,
+            EffectType::Burn => 2.0,
+            EffectType::Stun => 0.5,
+            EffectType::Freeze => 1.0,
+            EffectType::Poison => 4.0,
+            EffectType::Vulnerability => 3.0,
+            EffectType::ArmorShred => 5.0,
+        }
+    }
+
+    /// Potency the effect has before any bonus potency from the tower.
+    pub(crate) fn base_potency(&self) -> u32 {
+        match self {
+            EffectType::Vulnerability => 15,
+            EffectType::ArmorShred => 2,
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn stacking(&self) -> StackingPolicy {
+        match self {
+            EffectType::None | EffectType::Slow | EffectType::Stun => StackingPolicy::Refresh,
+            EffectType::Burn | EffectType::Freeze | EffectType::Vulnerability => {
+                StackingPolicy::KeepStrongest
+            }
+            EffectType::Poison => StackingPolicy::StackIntensity(5),
+            EffectType::ArmorShred => StackingPolicy::StackIntensity(10),
+        }
+    }
+
+    /// Whether the effect ticks damage over its duration.
+    pub(crate) fn deals_damage(&self) -> bool {
+        matches!(self, EffectType::Burn | EffectType::Poison)
+    }
+
+    /// Whether the effect stops the unit from moving.
+    pub(crate) fn immobilizes(&self) -> bool {
+        matches!(self, EffectType::Stun | EffectType::Freeze)
+    }
+
+    pub(crate) fn color(&self) -> Color {
+        match self {
+            EffectType::None => Color::NONE,
+            EffectType::Slow => Color::CYAN,
+            EffectType::Burn => Color::ORANGE_RED,
+            EffectType::Stun => Color::YELLOW,
+            EffectType::Freeze => Color::TURQUOISE,
+            EffectType::Poison => Color::LIME_GREEN,
+            EffectType::Vulnerability => Color::FUCHSIA,
+            EffectType::ArmorShred => Color::GRAY,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct StatusEffect {
+    pub(crate) effect_type: EffectType,
+    pub(crate) timer: Timer,
+    pub(crate) potency: u32,
+    pub(crate) stacks: u32,
+    /// Interval between damage ticks for damage-over-time effects.
+    pub(crate) tick: Timer,
+    /// The tower that applied the effect, credited with any kill it causes.
+    pub(crate) source: Option<Entity>,
+}
+
+impl StatusEffect {
+    pub(crate) fn new(
+        effect_type: EffectType,
+        duration: f32,
+        potency: u32,
+        source: Option<Entity>,
+    ) -> Self {
+        StatusEffect {
+            effect_type,
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+            potency,
+            stacks: 1,
+            tick: Timer::from_seconds(0.5, TimerMode::Repeating),
+            source,
+        }
+    }
+
+    /// Potency multiplied by the number of stacks.
+    pub(crate) fn intensity(&self) -> u32 {
+        self.potency.saturating_mul(self.stacks)
+    }
+
+    /// Damage a single damage-over-time tick deals.
+    pub(crate) fn tick_damage(&self) -> usize {
+        (1 + self.potency as usize) * self.stacks as usize
+    }
+
+    /// Combine a new application of the same effect according to its stacking policy.
+    pub(crate) fn stack(&mut self, effect: StatusEffect) {
+        match self.effect_type.stacking() {
+            StackingPolicy::Refresh => {
+                *self = effect;
+            }
+            StackingPolicy::StackIntensity(max_stacks) => {
+                self.stacks = (self.stacks + 1).min(max_stacks);
+                self.potency = self.potency.max(effect.potency);
+                self.timer = effect.timer;
+                self.source = effect.source.or(self.source);
+            }
+            StackingPolicy::KeepStrongest => {
+                if effect.potency >= self.potency {
+                    self.potency = effect.potency;
+                    self.source = effect.source.or(self.source);
+                }
+                if effect.timer.remaining() > self.timer.remaining() {
+                    self.timer = effect.timer;
+                }
+            }
+        }
+    }
+}
+
+impl Hash for StatusEffect {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.effect_type.hash(state);
+    }
+}
+
+impl PartialEq for StatusEffect {
+    fn eq(&self, other: &Self) -> bool {
+        self.effect_type == other.effect_type
+    }
+}
+
+impl Eq for StatusEffect {}
+
+pub(crate) fn status_effect_system(
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut EnemyUnit)>,
+    mut mob_despawn_event: EventWriter<MobDespawnEvent>,
+    mut enemy_schedule: ResMut<ScheduledForDespawnEnemy>,
+    mut tower_level_up_events: EventWriter<TowerLevelUp>,
+    mut tower_query: Query<(&TowerComponents, &mut TowerProgress)>,
+    mut tower_info: ResMut<TowerInfo>,
+) {
+    for (entity, mut enemy_unit) in query.iter_mut() {
+        if enemy_schedule.contains(&entity) || enemy_unit.status_effects.is_empty() {
+            continue;
+        }
+
+        let mut damage_ticks = Vec::new();
+        enemy_unit.status_effects.retain(|effect_type, effect| {
+            effect.timer.tick(time.delta());
+            if effect_type.deals_damage() {
+                let ticks = effect.tick.tick(time.delta()).times_finished_this_tick();
+                if ticks > 0 {
+                    damage_ticks.push((effect.tick_damage() * ticks as usize, effect.source));
+                }
+            }
+            !effect.timer.finished()
+        });
+
+        for (damage, source) in damage_ticks {
+            let dealt = enemy_unit.take_damage(damage);
+            if let Some((tower, mut progress)) =
+                source.and_then(|tower| tower_query.get_mut(tower).ok())
+            {
+                tower_info.add_damage(&tower.tower, dealt as u32);
+                progress.add_damage(dealt as u32);
+            }
+
+            if enemy_unit.health == 0 {
+                mob_despawn_event.send(MobDespawnEvent {
+                    enemy_entity: entity,
+                    spawner_id: enemy_unit.spwawner_id,
+                    reason: EnemyDespawnReason::Killed,
+                });
+                enemy_schedule.insert(entity);
+
+                // Credit the kill to the tower whose effect dealt the final tick
+                if let Some(tower_entity) = source {
+                    tower_level_up_events.send(TowerLevelUp {
+                        entity: tower_entity,
+                        reason: TowerLevelUpReason::Kill,
+                        enemy_experience: enemy_unit.experience,
+                    });
+                }
+                break;
+            }
+        }
+    }
+}
+
+pub(crate) fn draw_status_indicator_system(
+    query: Query<(&Transform, &EnemyUnit)>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, enemy_unit) in query.iter() {
+        // A row of small dots just above the unit, one per active effect
+        let origin = transform.translation.truncate() + Vec2::new(-6.0, 12.0);
+        for (index, effect_type) in enemy_unit.status_effects.keys().enumerate() {
+            gizmos.circle_2d(
+                origin + Vec2::X * 4.0 * index as f32,
+                1.5,
+                effect_type.color(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mob::{Enemies, SpawnId};
+
+    use super::*;
+
+    #[test]
+    fn test_stacking_policies() {
+        let mut slow = StatusEffect::new(EffectType::Slow, 2.0, 10, None);
+        slow.stack(StatusEffect::new(EffectType::Slow, 1.0, 5, None));
+        assert_eq!(slow.potency, 5);
+
+        let mut poison = StatusEffect::new(EffectType::Poison, 4.0, 2, None);
+        for _ in 0..10 {
+            poison.stack(StatusEffect::new(EffectType::Poison, 4.0, 1, None));
+        }
+        assert_eq!(poison.stacks, 5);
+        assert_eq!(poison.intensity(), 10);
+
+        let mut burn = StatusEffect::new(EffectType::Burn, 5.0, 8, None);
+        burn.stack(StatusEffect::new(EffectType::Burn, 2.0, 3, None));
+        assert_eq!(burn.potency, 8);
+        assert_eq!(burn.timer.duration().as_secs_f32(), 5.0);
+    }
+
+    #[test]
+    fn test_armor_and_shred() {
+        // Level 30 blocks have 3 armor
+        let mut unit = Enemies::Block.into_unit(SpawnId { id: 0 }, 30);
+        assert_eq!(unit.damage_taken(10), 7);
+        assert_eq!(unit.damage_taken(2), 1);
+
+        let shred = EffectType::ArmorShred.base_potency();
+        unit.insert_status(StatusEffect::new(EffectType::ArmorShred, 5.0, shred, None));
+        assert_eq!(unit.armor(), 1);
+        assert_eq!(unit.damage_taken(10), 9);
+        unit.insert_status(StatusEffect::new(EffectType::ArmorShred, 5.0, shred, None));
+        assert_eq!(unit.armor(), 0);
+        assert_eq!(unit.damage_taken(10), 10);
+
+        let vulnerability = EffectType::Vulnerability.base_potency();
+        unit.insert_status(StatusEffect::new(
+            EffectType::Vulnerability,
+            3.0,
+            vulnerability,
+            None,
+        ));
+        assert_eq!(unit.damage_taken(20), 23);
+        assert_eq!(unit.take_damage(20), 23);
+    }
+}
@@ -1,6 +1,12 @@
 //! Player module.
 
-use crate::{prelude::*, ui::level::LevelMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    prelude::*,
+    session::GameSettings,
+    state::{AppState, GameplaySet, StateDespawnMarker, IN_GAME},
+};
 
 /// The player plugin
 pub struct PlayerPlugin;
@@ -8,14 +14,14 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<PlayerUpdateEvent>()
-            .add_systems(Startup, create)
-            .add_systems(Update, update)
-            .add_systems(Update, level_down_on_death);
+            .add_systems(OnEnter(AppState::GameCreate), create)
+            .add_systems(FixedUpdate, update.in_set(GameplaySet))
+            .add_systems(FixedUpdate, game_over_on_death.in_set(GameplaySet));
     }
 }
 
 // Player Components
-#[derive(Debug, Clone, Component)]
+#[derive(Debug, Clone, PartialEq, Component, Serialize, Deserialize)]
 pub(crate) struct Player {
     pub(crate) hp: u32,
     pub(crate) bricks: u32,
@@ -43,10 +49,16 @@ pub(crate) enum PlayerUpdateEvent {
     Damage(u32),
     Bricks(u32),
     Build(u32),
+    /// Bricks returned for selling a tower.
+    Refund(u32),
 }
 
-fn create(mut commands: Commands) {
-    commands.spawn(Player::default());
+fn create(mut commands: Commands, settings: Res<GameSettings>) {
+    let player = Player {
+        hp: settings.difficulty.player_hp(),
+        bricks: settings.difficulty.starting_bricks(),
+    };
+    commands.spawn((player, StateDespawnMarker(IN_GAME)));
 }
 
 fn update(mut player_update_event: EventReader<PlayerUpdateEvent>, mut player: Query<&mut Player>) {
@@ -54,7 +66,7 @@ fn update(mut player_update_event: EventReader<PlayerUpdateEvent>, mut player: Q
     for event in player_update_event.read() {
         match event {
             PlayerUpdateEvent::Damage(damage) => player.hp = player.hp.saturating_sub(*damage),
-            PlayerUpdateEvent::Bricks(bricks) => {
+            PlayerUpdateEvent::Bricks(bricks) | PlayerUpdateEvent::Refund(bricks) => {
                 player.bricks = player.bricks.saturating_add(*bricks)
             }
             PlayerUpdateEvent::Build(bricks) => {
@@ -72,11 +84,8 @@ fn update(mut player_update_event: EventReader<PlayerUpdateEvent>, mut player: Q
     }
 }
 
-fn level_down_on_death(mut player: Query<&mut Player>, mut level_up_map: EventWriter<LevelMap>) {
-    // Could be faster to use a filter_map here or an event
-    let mut player = player.single_mut();
-    if player.hp == 0 {
-        level_up_map.send(LevelMap::LevelDown(5));
-        player.hp = 100;
+fn game_over_on_death(player: Query<&Player>, mut next_state: ResMut<NextState<AppState>>) {
+    if player.single().hp == 0 {
+        next_state.set(AppState::GameOver);
     }
 }
@@ -0,0 +1,353 @@
+//! Recording the player's commands and playing them back
+//!
+//! A replay is the game settings plus every command with the fixed step it was played on, the
+//! seed makes the rest of the game play out the same.
+
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::{
+        grid::{GridClickEvent, GridResource},
+        path_finding::to_transform,
+    },
+    prelude::*,
+    save::{read_ron, write_ron, SaveGame, SaveMessage},
+    session::GameSettings,
+    state::{AppState, GameSpeed, GameplaySet},
+    towers::{traps::TrapTypes, TowerTypes},
+};
+
+/// Where replays are saved and watched from.
+pub(crate) const REPLAY_FILE: &str = "replay.ron";
+
+/// The replay plugin.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReplayEvent>()
+            .init_resource::<Tick>()
+            .add_systems(OnEnter(AppState::GameCreate), start_recording)
+            .add_systems(OnEnter(AppState::StartMenu), stop_playback)
+            .add_systems(
+                FixedPreUpdate,
+                playback_system.run_if(
+                    in_state(AppState::GameRunning).and_then(resource_exists::<ReplayPlayback>),
+                ),
+            )
+            .add_systems(FixedUpdate, record_system.in_set(GameplaySet))
+            .add_systems(
+                FixedLast,
+                tick_system.run_if(in_state(AppState::GameRunning)),
+            )
+            .add_systems(
+                Update,
+                record_speed_system.run_if(
+                    resource_exists::<ReplayRecording>.and_then(resource_changed::<GameSpeed>),
+                ),
+            )
+            .add_systems(Update, replay_system.run_if(on_event::<ReplayEvent>()));
+    }
+}
+
+/// Sent by the menus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) enum ReplayEvent {
+    Save,
+    Watch,
+}
+
+/// Fixed steps played in the current game.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub(crate) struct Tick(pub(crate) u64);
+
+/// A player command, tiles are (x, y).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum ReplayCommand {
+    BuildTower(TowerTypes, (u32, u32)),
+    BuildTrap(TrapTypes, (u32, u32)),
+    RemoveTrap((u32, u32)),
+    UpgradeTower(TowerTypes, (u32, u32)),
+    UpgradeBranch((u32, u32), usize),
+    RemoveTower(TowerTypes, (u32, u32)),
+    ResearchTower(TowerTypes),
+    Speed(GameSpeed),
+}
+
+impl ReplayCommand {
+    fn from_click(event: &GridClickEvent) -> Option<Self> {
+        let tile = |pos: &TilePos| (pos.x, pos.y);
+        match event {
+            GridClickEvent::BuildTower(tower, _, pos) => {
+                Some(ReplayCommand::BuildTower(*tower, tile(pos)))
+            }
+            GridClickEvent::BuildTrap(trap, _, pos) => {
+                Some(ReplayCommand::BuildTrap(*trap, tile(pos)))
+            }
+            GridClickEvent::RemoveTrap(pos) => Some(ReplayCommand::RemoveTrap(tile(pos))),
+            GridClickEvent::UpgradeTower(tower, pos) => {
+                Some(ReplayCommand::UpgradeTower(*tower, tile(pos)))
+            }
+            GridClickEvent::UpgradeBranch(pos, branch) => {
+                Some(ReplayCommand::UpgradeBranch(tile(pos), *branch))
+            }
+            GridClickEvent::RemoveTower(tower, pos) => {
+                Some(ReplayCommand::RemoveTower(*tower, tile(pos)))
+            }
+            GridClickEvent::ResearchTower(tower) => Some(ReplayCommand::ResearchTower(*tower)),
+            // Selecting tiles changes nothing in the game
+            GridClickEvent::Highlight(..) | GridClickEvent::DeHighlight(..) => None,
+        }
+    }
+
+    fn to_click(self, grid: &GridResource) -> Option<GridClickEvent> {
+        let pos = |(x, y): (u32, u32)| TilePos::new(x, y);
+        let event = match self {
+            ReplayCommand::BuildTower(tower, tile) => {
+                GridClickEvent::BuildTower(tower, to_transform(pos(tile), grid), pos(tile))
+            }
+            ReplayCommand::BuildTrap(trap, tile) => {
+                GridClickEvent::BuildTrap(trap, to_transform(pos(tile), grid), pos(tile))
+            }
+            ReplayCommand::RemoveTrap(tile) => GridClickEvent::RemoveTrap(pos(tile)),
+            ReplayCommand::UpgradeTower(tower, tile) => {
+                GridClickEvent::UpgradeTower(tower, pos(tile))
+            }
+            ReplayCommand::UpgradeBranch(tile, branch) => {
+                GridClickEvent::UpgradeBranch(pos(tile), branch)
+            }
+            ReplayCommand::RemoveTower(tower, tile) => {
+                GridClickEvent::RemoveTower(tower, pos(tile))
+            }
+            ReplayCommand::ResearchTower(tower) => GridClickEvent::ResearchTower(tower),
+            ReplayCommand::Speed(_) => return None,
+        };
+        Some(event)
+    }
+}
+
+/// Everything needed to play a game again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Replay {
+    pub(crate) settings: GameSettings,
+    /// Commands in the order they were played, each with its tick.
+    pub(crate) commands: Vec<(u64, ReplayCommand)>,
+}
+
+/// The replay of the current game, missing for loaded games and while watching a replay.
+#[derive(Debug, Clone, Resource)]
+pub(crate) struct ReplayRecording(pub(crate) Replay);
+
+/// A replay being watched and the index of its next command, kept until the player leaves the game.
+#[derive(Debug, Clone, Resource)]
+pub(crate) struct ReplayPlayback {
+    pub(crate) replay: Replay,
+    pub(crate) next: usize,
+}
+
+impl ReplayPlayback {
+    pub(crate) fn new(replay: Replay) -> Self {
+        ReplayPlayback { replay, next: 0 }
+    }
+}
+
+fn start_recording(
+    mut commands: Commands,
+    settings: Res<GameSettings>,
+    speed: Res<GameSpeed>,
+    save: Option<Res<SaveGame>>,
+    playback: Option<ResMut<ReplayPlayback>>,
+) {
+    commands.insert_resource(Tick::default());
+    // A loaded game doesn't start from its seed, so it can't be replayed
+    if save.is_some() {
+        commands.remove_resource::<ReplayPlayback>();
+        commands.remove_resource::<ReplayRecording>();
+        return;
+    }
+    // Restarting while watching a replay watches it again
+    if let Some(mut playback) = playback {
+        playback.next = 0;
+        commands.remove_resource::<ReplayRecording>();
+        return;
+    }
+    commands.insert_resource(ReplayRecording(Replay {
+        settings: *settings,
+        commands: vec![(0, ReplayCommand::Speed(*speed))],
+    }));
+}
+
+fn stop_playback(mut commands: Commands) {
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+fn tick_system(mut tick: ResMut<Tick>) {
+    tick.0 += 1;
+}
+
+// Runs before the gameplay systems of the step, so commands land on the tick they were recorded on
+fn playback_system(
+    tick: Res<Tick>,
+    grid: Res<GridResource>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut speed: ResMut<GameSpeed>,
+    mut grid_events: EventWriter<GridClickEvent>,
+) {
+    if playback.next >= playback.replay.commands.len() {
+        return;
+    }
+    while let Some((at, command)) = playback.replay.commands.get(playback.next).copied() {
+        if at > tick.0 {
+            break;
+        }
+        playback.next += 1;
+        match command {
+            ReplayCommand::Speed(game_speed) => {
+                speed.set_if_neq(game_speed);
+            }
+            command => {
+                if let Some(event) = command.to_click(&grid) {
+                    grid_events.send(event);
+                }
+            }
+        }
+    }
+    if playback.next == playback.replay.commands.len() {
+        info!("Replay finished");
+    }
+}
+
+fn record_system(
+    tick: Res<Tick>,
+    mut grid_events: EventReader<GridClickEvent>,
+    recording: Option<ResMut<ReplayRecording>>,
+) {
+    let Some(mut recording) = recording else {
+        return;
+    };
+    for event in grid_events.read() {
+        if let Some(command) = ReplayCommand::from_click(event) {
+            recording.0.commands.push((tick.0, command));
+        }
+    }
+}
+
+fn record_speed_system(
+    tick: Res<Tick>,
+    speed: Res<GameSpeed>,
+    mut recording: ResMut<ReplayRecording>,
+) {
+    recording
+        .0
+        .commands
+        .push((tick.0, ReplayCommand::Speed(*speed)));
+}
+
+fn replay_system(
+    mut commands: Commands,
+    mut events: EventReader<ReplayEvent>,
+    recording: Option<Res<ReplayRecording>>,
+    mut message: ResMut<SaveMessage>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for event in events.read() {
+        let result = match event {
+            ReplayEvent::Save => match &recording {
+                Some(recording) => {
+                    write_ron(&recording.0, REPLAY_FILE).map(|_| "Replay saved".to_string())
+                }
+                None => Ok("Loaded games and replays are not recorded".to_string()),
+            },
+            ReplayEvent::Watch => read_ron::<Replay>(REPLAY_FILE).map(|replay| {
+                commands.insert_resource(replay.settings);
+                commands.insert_resource(ReplayPlayback::new(replay));
+                next_state.set(AppState::GameCreate);
+                "Watching replay".to_string()
+            }),
+        };
+        message.0 = Some(result.unwrap_or_else(|error| {
+            warn!("{}", error);
+            error.to_string()
+        }));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        player::Player,
+        session::{Difficulty, GameSummary},
+        sim::SimulationPlugin,
+        towers::TowerInfo,
+    };
+
+    use super::*;
+
+    fn app(settings: GameSettings) -> App {
+        let mut app = App::new();
+        app.add_plugins((SimulationPlugin, ReplayPlugin))
+            .insert_resource(settings);
+        app.finish();
+        app.cleanup();
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::GameCreate);
+        app
+    }
+
+    fn click(app: &mut App, event: impl Fn(Transform, TilePos) -> GridClickEvent) {
+        for (x, y) in [(18, 22), (22, 18), (26, 22)] {
+            let pos = TilePos::new(x, y);
+            let transform = to_transform(pos, app.world.resource::<GridResource>());
+            app.world.send_event(event(transform, pos));
+        }
+    }
+
+    #[test]
+    fn test_replay_plays_the_same_game() {
+        let settings = GameSettings {
+            difficulty: Difficulty::Easy,
+            seed: 3,
+            ..Default::default()
+        };
+        let mut played = app(settings);
+        for step in 0..900 {
+            match step {
+                10 => click(&mut played, |transform, pos| {
+                    GridClickEvent::BuildTower(TowerTypes::Basic, transform, pos)
+                }),
+                600 => click(&mut played, |_, pos| {
+                    GridClickEvent::UpgradeTower(TowerTypes::Basic, pos)
+                }),
+                _ => {}
+            }
+            played.update();
+        }
+        let replay = played.world.resource::<ReplayRecording>().0.clone();
+        let builds = replay
+            .commands
+            .iter()
+            .filter(|(_, command)| matches!(command, ReplayCommand::BuildTower(..)))
+            .count();
+        assert_eq!(builds, 3);
+
+        let mut watched = app(replay.settings);
+        watched.insert_resource(ReplayPlayback::new(replay));
+        for _ in 0..900 {
+            watched.update();
+        }
+        let playback = watched.world.resource::<ReplayPlayback>();
+        assert_eq!(playback.next, playback.replay.commands.len());
+        assert_eq!(
+            watched.world.resource::<GameSummary>(),
+            played.world.resource::<GameSummary>()
+        );
+        assert_eq!(
+            watched.world.resource::<TowerInfo>(),
+            played.world.resource::<TowerInfo>()
+        );
+        let player = |app: &mut App| app.world.query::<&Player>().single(&app.world).clone();
+        assert_eq!(player(&mut watched), player(&mut played));
+    }
+}
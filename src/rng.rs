@@ -0,0 +1,21 @@
+//! Random number generation
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::prelude::*;
+
+/// Seedable random number generator used by gameplay rolls.
+#[derive(Debug, Resource, Deref, DerefMut)]
+pub(crate) struct GameRng(StdRng);
+
+impl GameRng {
+    pub(crate) fn seeded(seed: u64) -> Self {
+        GameRng(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(StdRng::from_entropy())
+    }
+}
@@ -0,0 +1,427 @@
+//! Saving and loading a game in progress
+
+use std::{
+    fmt::{Display, Formatter},
+    fs, io,
+    path::Path,
+    time::Duration,
+};
+
+use bevy::ecs::system::SystemParam;
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    arena::{
+        grid::GridResource,
+        path_finding::{to_position, to_transform},
+    },
+    assets::SpriteAssets,
+    mob::{Enemies, EnemyComponent, MobSpawner, SpawnIds},
+    player::Player,
+    prelude::*,
+    session::{GameSettings, GameSummary},
+    state::{AppState, StateDespawnMarker, IN_GAME},
+    towers::{
+        check_placement, spawn_tower,
+        traps::{spawn_trap, Trap, TrapTypes},
+        upgrades::TowerUpgrades,
+        TowerComponents, TowerInfo, TowerPosition, TowerProgress, TowerSpending, TowerTypes,
+    },
+    ui::level::MapLevel,
+    weapons::weapon::{ProjectileData, WeaponComponent},
+};
+
+/// Where the pause menu saves the game.
+pub(crate) const SAVE_FILE: &str = "savegame.ron";
+
+/// The save plugin.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveEvent>()
+            .init_resource::<SaveMessage>()
+            .add_systems(Update, save_system.run_if(on_event::<SaveEvent>()))
+            .add_systems(OnEnter(AppState::StartMenu), clear_message)
+            .add_systems(OnEnter(AppState::GamePaused), clear_message)
+            .add_systems(OnEnter(AppState::GameOver), clear_message)
+            .add_systems(
+                OnEnter(AppState::GameRunning),
+                restore_system.run_if(resource_exists::<SaveGame>),
+            );
+    }
+}
+
+/// Sent by the pause menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) enum SaveEvent {
+    Save,
+    Load,
+}
+
+/// How the last save, load or replay file access went, shown in the menus.
+#[derive(Debug, Clone, Default, Resource)]
+pub(crate) struct SaveMessage(pub(crate) Option<String>);
+
+#[derive(Debug)]
+pub(crate) enum SaveError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    Parse(ron::error::SpannedError),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "Could not access the file: {}", error),
+            SaveError::Serialize(error) => write!(f, "Could not write the file: {}", error),
+            SaveError::Parse(error) => write!(f, "The file is damaged: {}", error),
+        }
+    }
+}
+
+pub(crate) fn write_ron<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(SaveError::Serialize)?;
+    fs::write(path, text).map_err(SaveError::Io)
+}
+
+pub(crate) fn read_ron<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, SaveError> {
+    let text = fs::read_to_string(path).map_err(SaveError::Io)?;
+    ron::from_str(&text).map_err(SaveError::Parse)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedWeapon {
+    projectile_data: ProjectileData,
+    /// Reload time in seconds, upgrades shorten it.
+    reload: f32,
+    range: f32,
+    level: u32,
+}
+
+impl From<&WeaponComponent> for SavedWeapon {
+    fn from(weapon: &WeaponComponent) -> Self {
+        SavedWeapon {
+            projectile_data: weapon.projectile_data,
+            reload: weapon.reload_timer.duration().as_secs_f32(),
+            range: weapon.range,
+            level: weapon.level,
+        }
+    }
+}
+
+impl From<&SavedWeapon> for WeaponComponent {
+    fn from(saved: &SavedWeapon) -> Self {
+        // The timer a fresh weapon of this type gets, with the upgraded reload time
+        let mut reload_timer = Timer::from(saved.projectile_data.weapon_type);
+        reload_timer.set_duration(Duration::from_secs_f32(saved.reload));
+        WeaponComponent {
+            projectile_data: saved.projectile_data,
+            reload_timer,
+            range: saved.range,
+            level: saved.level,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedTower {
+    tower: TowerTypes,
+    /// Bottom left tile of the footprint.
+    tile: (u32, u32),
+    progress: TowerProgress,
+    upgrades: TowerUpgrades,
+    spending: TowerSpending,
+    weapon: Option<SavedWeapon>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedTrap {
+    trap: TrapTypes,
+    tile: (u32, u32),
+    charges: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedSpawner {
+    pub(crate) enemy: Enemies,
+    /// Seconds between spawns.
+    pub(crate) period: f32,
+    pub(crate) max_count: usize,
+    pub(crate) max_kill: usize,
+    pub(crate) kills: usize,
+    pub(crate) id: usize,
+    pub(crate) level: u32,
+}
+
+/// A game in progress, enemies and projectiles on the field are left out.
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+pub(crate) struct SaveGame {
+    settings: GameSettings,
+    level: MapLevel,
+    player: Player,
+    summary: GameSummary,
+    tower_info: TowerInfo,
+    /// Grid occupancy is rebuilt from the tower footprints.
+    towers: Vec<SavedTower>,
+    traps: Vec<SavedTrap>,
+    spawners: Vec<SavedSpawner>,
+    next_spawn_id: usize,
+}
+
+/// Everything a save is taken from.
+#[derive(SystemParam)]
+pub(crate) struct CurrentGame<'w, 's> {
+    settings: Res<'w, GameSettings>,
+    map_level: Res<'w, MapLevel>,
+    summary: Res<'w, GameSummary>,
+    tower_info: Res<'w, TowerInfo>,
+    spawn_ids: Res<'w, SpawnIds>,
+    player: Query<'w, 's, &'static Player>,
+    towers: Query<
+        'w,
+        's,
+        (
+            &'static TowerComponents,
+            &'static TowerPosition,
+            &'static TowerProgress,
+            &'static TowerUpgrades,
+            &'static TowerSpending,
+            Option<&'static WeaponComponent>,
+        ),
+    >,
+    traps: Query<'w, 's, &'static Trap>,
+    spawners: Query<'w, 's, &'static EnemyComponent>,
+}
+
+impl CurrentGame<'_, '_> {
+    pub(crate) fn capture(&self) -> SaveGame {
+        let mut towers = self
+            .towers
+            .iter()
+            .map(
+                |(tower, position, progress, upgrades, spending, weapon)| SavedTower {
+                    tower: tower.tower,
+                    tile: (position.x, position.y),
+                    progress: progress.clone(),
+                    upgrades: *upgrades,
+                    spending: *spending,
+                    weapon: weapon.map(SavedWeapon::from),
+                },
+            )
+            .collect::<Vec<_>>();
+        // Query order is not stable across worlds, the same game should give the same file
+        towers.sort_by_key(|tower| tower.tile);
+        let mut traps = self
+            .traps
+            .iter()
+            .map(|trap| SavedTrap {
+                trap: trap.trap_type,
+                tile: (trap.tile.x, trap.tile.y),
+                charges: trap.charges,
+            })
+            .collect::<Vec<_>>();
+        traps.sort_by_key(|trap| trap.tile);
+        let mut spawners = self
+            .spawners
+            .iter()
+            .map(|enemy| enemy.spawner.saved())
+            .collect::<Vec<_>>();
+        spawners.sort_by_key(|spawner| spawner.id);
+
+        SaveGame {
+            settings: *self.settings,
+            level: self.map_level.clone(),
+            player: self.player.single().clone(),
+            summary: self.summary.clone(),
+            tower_info: self.tower_info.clone(),
+            towers,
+            traps,
+            spawners,
+            next_spawn_id: self.spawn_ids.0,
+        }
+    }
+}
+
+fn save_system(
+    mut commands: Commands,
+    mut events: EventReader<SaveEvent>,
+    game: CurrentGame,
+    mut message: ResMut<SaveMessage>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for event in events.read() {
+        let result = match event {
+            SaveEvent::Save => {
+                write_ron(&game.capture(), SAVE_FILE).map(|_| "Game saved".to_string())
+            }
+            SaveEvent::Load => read_ron::<SaveGame>(SAVE_FILE).map(|save| {
+                // A new game is created from the saved settings, the rest is restored once it runs
+                commands.insert_resource(save.settings);
+                commands.insert_resource(save);
+                next_state.set(AppState::GameCreate);
+                "Game loaded".to_string()
+            }),
+        };
+        message.0 = Some(result.unwrap_or_else(|error| {
+            warn!("{}", error);
+            error.to_string()
+        }));
+    }
+}
+
+fn clear_message(mut message: ResMut<SaveMessage>) {
+    message.0 = None;
+}
+
+fn restore_system(
+    mut commands: Commands,
+    save: Res<SaveGame>,
+    assets: Res<SpriteAssets>,
+    mut grid: ResMut<GridResource>,
+    mut player: Query<&mut Player>,
+) {
+    commands.remove_resource::<SaveGame>();
+    commands.insert_resource(save.level.clone());
+    commands.insert_resource(save.summary.clone());
+    commands.insert_resource(save.tower_info.clone());
+    commands.insert_resource(SpawnIds(save.next_spawn_id));
+    *player.single_mut() = save.player.clone();
+
+    for saved in save.towers.iter() {
+        let pos = TilePos::new(saved.tile.0, saved.tile.1);
+        let tiles = match check_placement(&grid, &saved.tower, &pos) {
+            Ok(tiles) => tiles,
+            Err(error) => {
+                warn!("Saved {} tower left out: {}", saved.tower, error);
+                continue;
+            }
+        };
+        let transform = to_transform(pos, &grid);
+        let entity = spawn_tower(
+            &mut commands,
+            &assets,
+            &grid,
+            &saved.tower,
+            &transform,
+            pos,
+            saved.spending,
+        );
+        let mut tower = commands.entity(entity);
+        tower.insert((saved.progress.clone(), saved.upgrades));
+        if let Some(weapon) = &saved.weapon {
+            tower.insert(WeaponComponent::from(weapon));
+        }
+        grid.set_area_occupied(&tiles, entity);
+    }
+
+    for saved in save.traps.iter() {
+        let pos = TilePos::new(saved.tile.0, saved.tile.1);
+        if grid.is_occupied(&pos) || grid.get_trap(pos).is_some() {
+            warn!("Saved {} trap left out: tile is taken", saved.trap);
+            continue;
+        }
+        let transform = to_transform(pos, &grid);
+        let entity = spawn_trap(
+            &mut commands,
+            &grid,
+            &saved.trap,
+            &transform,
+            pos,
+            saved.charges,
+        );
+        grid.set_trap(&pos, entity);
+    }
+
+    let spawn_position = to_position(grid.grid_enemy_start, &grid);
+    for saved in save.spawners.iter() {
+        commands.spawn((
+            EnemyComponent {
+                mob_type: saved.enemy,
+                spawner: MobSpawner::restore(saved, spawn_position),
+            },
+            StateDespawnMarker(IN_GAME),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use bevy::ecs::system::SystemState;
+
+    use crate::{
+        mob::Wave,
+        sim::{Scenario, ScenarioTower, SimulationPlugin},
+    };
+
+    use super::*;
+
+    fn capture(world: &mut World) -> SaveGame {
+        let mut state = SystemState::<CurrentGame>::new(world);
+        state.get(world).capture()
+    }
+
+    #[test]
+    fn test_restore_saved_game() {
+        let scenario = Scenario {
+            seed: 7,
+            towers: ScenarioTower::along_path(TowerTypes::Basic, 4, 3),
+            waves: vec![Wave {
+                enemy: Enemies::Block,
+                level: 2,
+                count: 60,
+            }],
+            ..Default::default()
+        };
+        let mut app = scenario.app();
+        for _ in 0..600 {
+            app.update();
+        }
+        let save = capture(&mut app.world);
+        assert_eq!(save.towers.len(), 4);
+        assert_eq!(save.spawners.len(), 1);
+
+        let text = ron::to_string(&save).unwrap();
+        assert_eq!(ron::from_str::<SaveGame>(&text).unwrap(), save);
+
+        let mut app = App::new();
+        app.add_plugins((SimulationPlugin, SavePlugin))
+            .insert_resource(save.settings)
+            .insert_resource(save.clone());
+        app.finish();
+        app.cleanup();
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::GameCreate);
+        app.update();
+        app.update();
+
+        // One step has run since the restore, no enemy is on the field yet
+        let restored = capture(&mut app.world);
+        assert_eq!(restored.towers, save.towers);
+        assert_eq!(restored.tower_info, save.tower_info);
+        assert_eq!(restored.player, save.player);
+        assert_eq!(restored.level, save.level);
+        assert_eq!(restored.spawners, save.spawners);
+
+        // Restored weapons reload after every shot, not just the first
+        let mut weapons = app.world.query::<(Entity, &WeaponComponent)>();
+        let mut shots = HashMap::<Entity, (f32, u32)>::new();
+        for _ in 0..1200 {
+            app.update();
+            for (entity, weapon) in weapons.iter(&app.world) {
+                let elapsed = weapon.reload_timer.elapsed_secs();
+                let (last, count) = shots.entry(entity).or_insert((elapsed, 0));
+                if elapsed < *last {
+                    *count += 1;
+                }
+                *last = elapsed;
+            }
+        }
+        assert!(shots.values().any(|(_, count)| *count > 2));
+    }
+}
@@ -0,0 +1,272 @@
+//! Local high-score table
+
+use std::{cmp::Ordering, fmt::Display, io, path::Path};
+
+use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    player::Player,
+    prelude::*,
+    replay::ReplayPlayback,
+    save::{read_ron, write_ron, SaveError},
+    session::{Difficulty, GameSettings, GameSummary, MapLayout},
+    state::AppState,
+    ui::level::MapLevel,
+};
+
+/// Where finished runs are kept.
+pub(crate) const SCORES_FILE: &str = "highscores.ron";
+
+/// Runs kept in the table, the lowest levels are dropped first.
+const MAX_SCORES: usize = 100;
+
+/// Mixed into the checksum so it can't be recomputed from the entries alone.
+const CHECKSUM_SALT: &str = "rusty-apple high scores";
+
+/// The high scores plugin.
+pub struct ScoresPlugin;
+
+impl Plugin for ScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HighScores>()
+            .add_systems(Startup, load_scores)
+            .add_systems(OnEnter(AppState::GameOver), record_score);
+    }
+}
+
+/// A finished run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ScoreEntry {
+    pub(crate) map: MapLayout,
+    pub(crate) difficulty: Difficulty,
+    /// Highest map level reached.
+    pub(crate) level: u32,
+    pub(crate) kills: usize,
+    pub(crate) bricks_spent: u32,
+    /// Seconds of game time.
+    pub(crate) duration: f32,
+}
+
+/// Columns of the table, any of them can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Sequence)]
+pub(crate) enum ScoreColumn {
+    Map,
+    Difficulty,
+    #[default]
+    Level,
+    Kills,
+    BricksSpent,
+    Duration,
+}
+
+impl Display for ScoreColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScoreColumn::BricksSpent => write!(f, "Bricks Spent"),
+            ScoreColumn::Duration => write!(f, "Time"),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl ScoreColumn {
+    pub(crate) fn compare(&self, a: &ScoreEntry, b: &ScoreEntry) -> Ordering {
+        match self {
+            ScoreColumn::Map => a.map.cmp(&b.map),
+            ScoreColumn::Difficulty => a.difficulty.cmp(&b.difficulty),
+            ScoreColumn::Level => a.level.cmp(&b.level),
+            ScoreColumn::Kills => a.kills.cmp(&b.kills),
+            ScoreColumn::BricksSpent => a.bricks_spent.cmp(&b.bricks_spent),
+            ScoreColumn::Duration => a.duration.total_cmp(&b.duration),
+        }
+    }
+
+    pub(crate) fn value(&self, entry: &ScoreEntry) -> String {
+        match self {
+            ScoreColumn::Map => entry.map.to_string(),
+            ScoreColumn::Difficulty => entry.difficulty.to_string(),
+            ScoreColumn::Level => entry.level.to_string(),
+            ScoreColumn::Kills => entry.kills.to_string(),
+            ScoreColumn::BricksSpent => entry.bricks_spent.to_string(),
+            ScoreColumn::Duration => {
+                let seconds = entry.duration as u32;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Resource)]
+pub(crate) struct HighScores {
+    pub(crate) entries: Vec<ScoreEntry>,
+    /// The file on disk did not match its checksum, so its entries were ignored.
+    pub(crate) edited: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScoreFile {
+    entries: Vec<ScoreEntry>,
+    checksum: u64,
+}
+
+// FNV-1a, unlike the std hasher it gives the same value on every build
+fn checksum(entries: &[ScoreEntry]) -> Result<u64, SaveError> {
+    let text = ron::to_string(entries).map_err(SaveError::Serialize)?;
+    let hash = CHECKSUM_SALT
+        .bytes()
+        .chain(text.bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    Ok(hash)
+}
+
+impl HighScores {
+    pub(crate) fn add(&mut self, entry: ScoreEntry) {
+        self.entries.push(entry);
+        self.entries.sort_by(|a, b| {
+            ScoreColumn::Level
+                .compare(b, a)
+                .then(ScoreColumn::Kills.compare(b, a))
+        });
+        self.entries.truncate(MAX_SCORES);
+    }
+
+    pub(crate) fn sorted(&self, column: ScoreColumn, descending: bool) -> Vec<ScoreEntry> {
+        let mut entries = self.entries.clone();
+        entries.sort_by(|a, b| {
+            let order = column.compare(a, b);
+            if descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        entries
+    }
+
+    fn read(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let file = read_ron::<ScoreFile>(path)?;
+        if checksum(&file.entries)? == file.checksum {
+            Ok(HighScores {
+                entries: file.entries,
+                edited: false,
+            })
+        } else {
+            Ok(HighScores {
+                entries: Vec::new(),
+                edited: true,
+            })
+        }
+    }
+
+    fn write(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let file = ScoreFile {
+            entries: self.entries.clone(),
+            checksum: checksum(&self.entries)?,
+        };
+        write_ron(&file, path)
+    }
+}
+
+fn load_scores(mut commands: Commands) {
+    let scores = match HighScores::read(SCORES_FILE) {
+        Ok(scores) => scores,
+        Err(SaveError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            HighScores::default()
+        }
+        Err(error) => {
+            warn!("High scores not loaded: {}", error);
+            HighScores::default()
+        }
+    };
+    if scores.edited {
+        warn!("The high score file was edited, its scores are ignored");
+    }
+    commands.insert_resource(scores);
+}
+
+fn record_score(
+    mut scores: ResMut<HighScores>,
+    settings: Res<GameSettings>,
+    map_level: Res<MapLevel>,
+    summary: Res<GameSummary>,
+    player: Query<&Player>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    // Watching a replay is not a run of its own
+    if playback.is_some() {
+        return;
+    }
+    // Bricks only come from the start, kills and refunds, what is left over was not spent
+    let bricks = player.get_single().map_or(0, |player| player.bricks);
+    let bricks_spent = (settings.difficulty.starting_bricks()
+        + summary.bricks_earned
+        + summary.bricks_refunded)
+        .saturating_sub(bricks);
+    scores.add(ScoreEntry {
+        map: settings.map,
+        difficulty: settings.difficulty,
+        level: map_level.level,
+        kills: summary.kills,
+        bricks_spent,
+        duration: summary.time_survived,
+    });
+    if let Err(error) = scores.write(SCORES_FILE) {
+        warn!("High score not saved: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    fn entry(level: u32, kills: usize) -> ScoreEntry {
+        ScoreEntry {
+            map: MapLayout::Straight,
+            difficulty: Difficulty::Normal,
+            level,
+            kills,
+            bricks_spent: 40,
+            duration: 95.5,
+        }
+    }
+
+    #[test]
+    fn test_edited_scores_are_ignored() {
+        let path = std::env::temp_dir().join("rusty-apple-test-highscores.ron");
+        let mut scores = HighScores::default();
+        scores.add(entry(3, 20));
+        scores.add(entry(7, 90));
+        scores.write(&path).unwrap();
+
+        let read = HighScores::read(&path).unwrap();
+        assert!(!read.edited);
+        assert_eq!(read.entries, scores.entries);
+
+        let text = fs::read_to_string(&path)
+            .unwrap()
+            .replace("level: 7", "level: 70");
+        fs::write(&path, text).unwrap();
+        let read = HighScores::read(&path).unwrap();
+        assert!(read.edited);
+        assert!(read.entries.is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sort_by_column() {
+        let mut scores = HighScores::default();
+        scores.add(entry(3, 90));
+        scores.add(entry(7, 20));
+        assert_eq!(scores.entries[0].level, 7);
+
+        let by_kills = scores.sorted(ScoreColumn::Kills, true);
+        assert_eq!(by_kills[0].kills, 90);
+        let by_kills = scores.sorted(ScoreColumn::Kills, false);
+        assert_eq!(by_kills[0].kills, 20);
+    }
+}
@@ -0,0 +1,158 @@
+//! A game session: the settings it was started with and what happened in it
+
+use std::fmt::Display;
+
+use bevy_ecs_tilemap::tiles::TilePos;
+use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mob::{EnemyDespawnReason, MobDespawnEvent},
+    player::PlayerUpdateEvent,
+    prelude::*,
+    rng::GameRng,
+    state::{AppState, GameplaySet},
+};
+
+/// Where enemies enter and leave the grid.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Sequence, Serialize, Deserialize,
+)]
+pub(crate) enum MapLayout {
+    /// Right to left through the middle.
+    #[default]
+    Straight,
+    /// Corner to corner.
+    Diagonal,
+    /// Top to bottom through the middle.
+    Climb,
+}
+
+impl Display for MapLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl MapLayout {
+    /// Enemy start and end tiles on a grid of `grid_size` squares.
+    pub(crate) fn endpoints(&self, grid_size: u32) -> (TilePos, TilePos) {
+        let last = grid_size.saturating_sub(1);
+        let middle = grid_size / 2;
+        match self {
+            MapLayout::Straight => (TilePos::new(last, middle), TilePos::new(0, middle)),
+            MapLayout::Diagonal => (TilePos::new(last, last), TilePos::new(0, 0)),
+            MapLayout::Climb => (TilePos::new(middle, last), TilePos::new(middle, 0)),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Sequence, Serialize, Deserialize,
+)]
+pub(crate) enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Difficulty {
+    pub(crate) fn player_hp(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 150,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 60,
+        }
+    }
+
+    pub(crate) fn starting_bricks(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 10,
+            Difficulty::Normal => 5,
+            Difficulty::Hard => 3,
+        }
+    }
+}
+
+/// Choices made on the start screen, read when a game is created.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Serialize, Deserialize)]
+pub(crate) struct GameSettings {
+    pub(crate) map: MapLayout,
+    pub(crate) difficulty: Difficulty,
+    /// Seeds the game's random rolls, the same seed and inputs play out the same game.
+    pub(crate) seed: u64,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            map: MapLayout::default(),
+            difficulty: Difficulty::default(),
+            seed: rand::random(),
+        }
+    }
+}
+
+/// Totals of the current game shown on the game over screen.
+#[derive(Debug, Clone, Default, PartialEq, Resource, Serialize, Deserialize)]
+pub(crate) struct GameSummary {
+    pub(crate) kills: usize,
+    /// Enemies that reached the end of the path.
+    pub(crate) leaks: usize,
+    /// Bricks the player got for kills.
+    pub(crate) bricks_earned: u32,
+    /// Bricks returned for selling towers.
+    pub(crate) bricks_refunded: u32,
+    /// Seconds of game time, pauses excluded.
+    pub(crate) time_survived: f32,
+}
+
+/// The session plugin.
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSettings>()
+            .init_resource::<GameSummary>()
+            .add_systems(OnEnter(AppState::GameCreate), (reset_summary, seed_rng))
+            .add_systems(FixedUpdate, summary_system.in_set(GameplaySet));
+    }
+}
+
+fn reset_summary(mut commands: Commands) {
+    commands.insert_resource(GameSummary::default());
+}
+
+fn seed_rng(mut commands: Commands, settings: Res<GameSettings>) {
+    commands.insert_resource(GameRng::seeded(settings.seed));
+}
+
+fn summary_system(
+    time: Res<Time>,
+    mut summary: ResMut<GameSummary>,
+    mut despawn_events: EventReader<MobDespawnEvent>,
+    mut player_events: EventReader<PlayerUpdateEvent>,
+) {
+    summary.time_survived += time.delta_seconds();
+    for event in despawn_events.read() {
+        match event.reason {
+            EnemyDespawnReason::Killed => summary.kills += 1,
+            EnemyDespawnReason::ReachedEnd => summary.leaks += 1,
+        }
+    }
+    // Leaks are counted from despawns only, an enemy sent back to the start hurts the player again
+    for event in player_events.read() {
+        match event {
+            PlayerUpdateEvent::Bricks(bricks) => summary.bricks_earned += bricks,
+            PlayerUpdateEvent::Refund(bricks) => summary.bricks_refunded += bricks,
+            PlayerUpdateEvent::Damage(_) | PlayerUpdateEvent::Build(_) => {}
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Headless simulation
+//!
+//! Runs the game logic without a window, renderer or UI, one fixed step per update.
+
+use std::time::Duration;
+
+use bevy::{
+    hierarchy::HierarchyPlugin, input::InputPlugin, time::TimeUpdateStrategy,
+    transform::TransformPlugin,
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use enum_iterator::all;
+
+use crate::{
+    arena::{
+        grid::{grid_squares, GridClickEvent, GridResource},
+        path_finding::to_transform,
+        GridPlugin, PathFindingPlugin,
+    },
+    assets::{SpriteAssets, Tiles},
+    collision::CollisionPlugin,
+    mob::{MobPlugin, Wave, WaveScript},
+    player::{Player, PlayerPlugin},
+    prelude::*,
+    session::{GameSettings, GameSummary, SessionPlugin},
+    state::{AppState, RenderSet, StatesPlugin, TIMESTEP_HZ},
+    towers::{TowerComponents, TowerInfo, TowerPlugin, TowerSpending, TowerTypes},
+    ui::level::{LevelPlugin, MapLevel},
+    weapons::WeaponPlugin,
+};
+
+/// The game logic on top of `MinimalPlugins`, without egui, tilemap rendering or sprites.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let step = Duration::from_secs_f64(1.0 / TIMESTEP_HZ);
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            InputPlugin,
+            PhysicsPlugins::new(FixedUpdate),
+        ))
+        .insert_resource(Time::new_with(Physics::fixed_once_hz(TIMESTEP_HZ)))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(step))
+        .insert_resource(SpriteAssets::placeholder())
+        .insert_resource(Tiles(Handle::default()))
+        .add_plugins((
+            StatesPlugin,
+            SessionPlugin,
+            GridPlugin,
+            PathFindingPlugin,
+            MobPlugin,
+            TowerPlugin,
+            WeaponPlugin,
+            CollisionPlugin,
+            PlayerPlugin,
+            LevelPlugin,
+        ))
+        // Nothing is drawn headless
+        .configure_sets(Update, RenderSet.run_if(|| false));
+    }
+}
+
+/// A tower built before the first wave.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScenarioTower {
+    /// The kind of tower.
+    pub tower: TowerTypes,
+    /// Grid column of the tower's bottom left tile.
+    pub x: u32,
+    /// Grid row of the tower's bottom left tile.
+    pub y: u32,
+    /// Weapon upgrades bought right after building it.
+    pub upgrades: u32,
+}
+
+impl ScenarioTower {
+    /// Up to `count` towers of one type lined up on both sides of the default straight path.
+    pub fn along_path(tower: TowerTypes, count: usize, upgrades: u32) -> Vec<ScenarioTower> {
+        let squares = grid_squares();
+        let middle = squares / 2;
+        let size = tower.footprint();
+        (0..count as u32)
+            .map(|i| ScenarioTower {
+                tower,
+                x: 2 + (i / 2) * (size + 1),
+                y: if i % 2 == 0 {
+                    middle + 1
+                } else {
+                    middle - size
+                },
+                upgrades,
+            })
+            // Keep clear of the column the enemies enter from
+            .take_while(|placed| placed.x + size < squares)
+            .collect()
+    }
+}
+
+/// Tower types that deal damage, the ones worth balancing.
+pub fn armed_towers() -> Vec<TowerTypes> {
+    all::<TowerTypes>()
+        .filter(|tower| tower.weapon().is_some())
+        .collect()
+}
+
+/// Input to a headless run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    /// Seeds every random roll of the run.
+    pub seed: u64,
+    /// Towers are free, placements that don't fit or block the path are skipped.
+    pub towers: Vec<ScenarioTower>,
+    /// Played in order, the run ends after the last one.
+    pub waves: Vec<Wave>,
+    /// Seconds of game time after which the run gives up.
+    pub time_limit: f32,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            seed: 0,
+            towers: Vec::new(),
+            waves: Vec::new(),
+            time_limit: 600.0,
+        }
+    }
+}
+
+/// What happened in a headless run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulationSummary {
+    /// Waves whose kill count was reached.
+    pub waves_cleared: u32,
+    /// Enemies killed.
+    pub kills: usize,
+    /// Enemies that reached the end of the path.
+    pub leaks: usize,
+    /// Damage done by each tower type that has a weapon.
+    pub damage: Vec<(TowerTypes, u128)>,
+    /// Bricks spent building and upgrading each tower type.
+    pub spent: Vec<(TowerTypes, u32)>,
+    /// Bricks the player got for kills.
+    pub bricks_earned: u32,
+    /// Scenario towers that could be placed.
+    pub towers_built: usize,
+    /// Whether the player ran out of hp.
+    pub game_over: bool,
+    /// Seconds of game time played.
+    pub time: f32,
+}
+
+#[derive(Debug, Clone, Resource)]
+struct ScenarioTowers(Vec<ScenarioTower>);
+
+impl Scenario {
+    /// The app set to play the scenario, each update is one fixed step.
+    pub(crate) fn app(&self) -> App {
+        let mut app = App::new();
+        app.add_plugins(SimulationPlugin)
+            .insert_resource(GameSettings {
+                seed: self.seed,
+                ..Default::default()
+            })
+            .insert_resource(WaveScript(self.waves.clone()))
+            .insert_resource(ScenarioTowers(self.towers.clone()))
+            .add_systems(FixedUpdate, scenario_build_system.in_set(GameplaySet));
+        app.finish();
+        app.cleanup();
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::GameCreate);
+        app
+    }
+
+    /// Play the scenario until the waves are cleared, the player dies or time runs out.
+    pub fn run(&self) -> SimulationSummary {
+        let mut app = self.app();
+        let steps = (self.time_limit as f64 * TIMESTEP_HZ).ceil() as usize;
+        for _ in 0..steps {
+            app.update();
+            if self.finished(&app.world) {
+                break;
+            }
+        }
+        self.summary(&mut app.world)
+    }
+
+    fn finished(&self, world: &World) -> bool {
+        let level = world.resource::<MapLevel>().level as usize;
+        *world.resource::<State<AppState>>().get() == AppState::GameOver || level > self.waves.len()
+    }
+
+    fn summary(&self, world: &mut World) -> SimulationSummary {
+        let spending = world
+            .query::<(&TowerComponents, &TowerSpending)>()
+            .iter(world)
+            .map(|(tower, spending)| (tower.tower, spending.get_spent()))
+            .collect::<Vec<_>>();
+        let spent = all::<TowerTypes>()
+            .filter(|tower| spending.iter().any(|(built, _)| built == tower))
+            .map(|tower| {
+                let bricks = spending
+                    .iter()
+                    .filter(|(built, _)| *built == tower)
+                    .map(|(_, bricks)| bricks)
+                    .sum();
+                (tower, bricks)
+            })
+            .collect();
+        let towers_built = spending.len();
+        let game_summary = world.resource::<GameSummary>();
+        let tower_info = world.resource::<TowerInfo>();
+        let level = world.resource::<MapLevel>().level;
+        SimulationSummary {
+            waves_cleared: level.saturating_sub(1).min(self.waves.len() as u32),
+            kills: game_summary.kills,
+            leaks: game_summary.leaks,
+            damage: armed_towers()
+                .into_iter()
+                .map(|tower| (tower, tower_info.get_damage(&tower).damage()))
+                .collect(),
+            spent,
+            bricks_earned: game_summary.bricks_earned,
+            towers_built,
+            game_over: *world.resource::<State<AppState>>().get() == AppState::GameOver,
+            time: game_summary.time_survived,
+        }
+    }
+}
+
+// Builds the scenario on the first step and buys the upgrades once the towers stand
+fn scenario_build_system(
+    mut built: Local<bool>,
+    mut upgraded: Local<bool>,
+    scenario: Res<ScenarioTowers>,
+    grid: Res<GridResource>,
+    mut player: Query<&mut Player>,
+    mut grid_events: EventWriter<GridClickEvent>,
+) {
+    if !*built {
+        *built = true;
+        player.single_mut().bricks = u32::MAX / 2;
+        for tower in scenario.0.iter() {
+            let pos = TilePos::new(tower.x, tower.y);
+            let transform = to_transform(pos, &grid);
+            grid_events.send(GridClickEvent::BuildTower(tower.tower, transform, pos));
+        }
+        return;
+    }
+    if *upgraded {
+        return;
+    }
+    let standing = scenario
+        .0
+        .iter()
+        .filter(|tower| grid.get_tower(TilePos::new(tower.x, tower.y)).is_some())
+        .collect::<Vec<_>>();
+    if standing.is_empty() {
+        return;
+    }
+    *upgraded = true;
+    for tower in standing {
+        let pos = TilePos::new(tower.x, tower.y);
+        for _ in 0..tower.upgrades {
+            grid_events.send(GridClickEvent::UpgradeTower(tower.tower, pos));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mob::Enemies;
+
+    use super::*;
+
+    fn of_tower(damage: &[(TowerTypes, u128)], tower: TowerTypes) -> u128 {
+        damage
+            .iter()
+            .find(|(built, _)| *built == tower)
+            .map_or(0, |(_, damage)| *damage)
+    }
+
+    fn scenario(towers: Vec<ScenarioTower>) -> Scenario {
+        Scenario {
+            seed: 42,
+            towers,
+            waves: vec![
+                Wave {
+                    enemy: Enemies::Block,
+                    level: 1,
+                    count: 10,
+                },
+                Wave {
+                    enemy: Enemies::Block,
+                    level: 2,
+                    count: 20,
+                },
+            ],
+            time_limit: 120.0,
+        }
+    }
+
+    fn defence() -> Vec<ScenarioTower> {
+        [(18, 22), (22, 18), (26, 22)]
+            .into_iter()
+            .map(|(x, y)| ScenarioTower {
+                tower: TowerTypes::Basic,
+                x,
+                y,
+                upgrades: 2,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_game() {
+        let scenario = scenario(defence());
+        assert_eq!(scenario.run(), scenario.run());
+    }
+
+    #[test]
+    fn test_towers_kill_and_undefended_leaks() {
+        let defended = scenario(defence()).run();
+        assert_eq!(defended.towers_built, 3);
+        assert!(defended.kills > 0);
+
+        let undefended = scenario(Vec::new()).run();
+        assert_eq!(undefended.kills, 0);
+        assert_eq!(undefended.waves_cleared, 0);
+        assert!(undefended.leaks > 0);
+    }
+
+    #[test]
+    fn test_damage_is_what_enemies_lost() {
+        let mut scenario = scenario(defence());
+        scenario.waves.truncate(1);
+        let summary = scenario.run();
+        let damage = of_tower(&summary.damage, TowerTypes::Basic);
+        // Level 1 blocks have 3 hp, at most two are still walking when the run ends
+        assert!(damage >= summary.kills as u128 * 3);
+        assert!(damage <= (summary.kills + summary.leaks + 2) as u128 * 3);
+    }
+}
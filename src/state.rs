@@ -1,21 +1,174 @@
+//! Game states
 
+use std::fmt::Display;
 
+use bevy::ecs::schedule::ExecutorKind;
+use enum_iterator::{all, Sequence};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Gameplay and physics steps per second of game time.
+pub const TIMESTEP_HZ: f64 = 60.0;
+
+/// The states the game moves through.
 #[derive(States, Debug, Copy, Clone, Hash, Eq, PartialEq, Default, Sequence)]
 pub enum AppState {
+    /// Waiting for the player to start a game.
     #[default]
     StartMenu,
+    /// Resetting the world for a new game.
     GameCreate,
+    /// Playing.
     GameRunning,
+    /// Playing but paused.
     GamePaused,
+    /// The player lost.
     GameOver,
 }
 
+/// States a game session lives through, its entities are despawned on entering any other.
+pub(crate) const IN_GAME: &[AppState] = &[
+    AppState::GameRunning,
+    AppState::GamePaused,
+    AppState::GameOver,
+];
+
+/// Systems that only run while the game is running, simulation goes in `FixedUpdate`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct GameplaySet;
+
+/// Gameplay systems that only draw, part of `GameplaySet` in `Update`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct RenderSet;
+
+/// How fast gameplay runs, scales virtual time.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Resource, Sequence, Serialize, Deserialize,
+)]
+pub(crate) enum GameSpeed {
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+}
+
+impl Display for GameSpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x", self.factor())
+    }
+}
+
+impl GameSpeed {
+    pub(crate) fn factor(&self) -> f32 {
+        match self {
+            GameSpeed::Normal => 1.0,
+            GameSpeed::Double => 2.0,
+            GameSpeed::Quadruple => 4.0,
+        }
+    }
+}
+
+/// Despawns the entity on entering a state other than the listed ones.
+#[derive(Debug, Component, Clone, Copy)]
+pub(crate) struct StateDespawnMarker<S: States>(pub(crate) &'static [S]);
+
+/// The state plugin.
 pub struct StatesPlugin;
 
 impl Plugin for StatesPlugin {
     fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .init_resource::<GameSpeed>()
+            .insert_resource(Time::<Fixed>::from_hz(TIMESTEP_HZ))
+            .configure_sets(Update, GameplaySet.run_if(in_state(AppState::GameRunning)))
+            .configure_sets(Update, RenderSet.in_set(GameplaySet))
+            .configure_sets(
+                FixedUpdate,
+                GameplaySet.run_if(in_state(AppState::GameRunning)),
+            )
+            // Ambiguous systems always run in the same order, so a seed replays the same game
+            .edit_schedule(FixedUpdate, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            })
+            .add_systems(Update, state_input_system)
+            .add_systems(
+                Update,
+                speed_input_system.run_if(
+                    in_state(AppState::GameRunning).or_else(in_state(AppState::GamePaused)),
+                ),
+            )
+            .add_systems(
+                Update,
+                apply_speed_system.run_if(resource_changed::<GameSpeed>),
+            )
+            .add_systems(OnEnter(AppState::GameCreate), start_game)
+            .add_systems(OnEnter(AppState::GamePaused), pause_time)
+            .add_systems(OnExit(AppState::GamePaused), resume_time);
         for state in all::<AppState>() {
             app.add_systems(OnEnter(state), state_enter_despawn::<AppState>);
         }
     }
-}
\ No newline at end of file
+}
+
+fn state_enter_despawn<S: States>(
+    mut commands: Commands,
+    state: Res<State<S>>,
+    query: Query<(Entity, &StateDespawnMarker<S>)>,
+) {
+    for (entity, marker) in query.iter() {
+        if !marker.0.contains(state.get()) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Every plugin resets its own resources on entering GameCreate, then the game starts
+fn start_game(mut next_state: ResMut<NextState<AppState>>) {
+    next_state.set(AppState::GameRunning);
+}
+
+fn state_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    match state.get() {
+        AppState::StartMenu | AppState::GameOver if keys.just_pressed(KeyCode::Enter) => {
+            next_state.set(AppState::GameCreate);
+        }
+        AppState::GameRunning if keys.just_pressed(KeyCode::KeyP) => {
+            next_state.set(AppState::GamePaused);
+        }
+        AppState::GamePaused if keys.just_pressed(KeyCode::KeyP) => {
+            next_state.set(AppState::GameRunning);
+        }
+        _ => {}
+    }
+}
+
+fn speed_input_system(keys: Res<ButtonInput<KeyCode>>, mut speed: ResMut<GameSpeed>) {
+    let keys_speeds = [
+        (KeyCode::Digit1, GameSpeed::Normal),
+        (KeyCode::Digit2, GameSpeed::Double),
+        (KeyCode::Digit3, GameSpeed::Quadruple),
+    ];
+    for (key, game_speed) in keys_speeds {
+        if keys.just_pressed(key) {
+            *speed = game_speed;
+        }
+    }
+}
+
+// Fixed steps and the physics inside them follow virtual time, a faster clock just runs more steps
+fn apply_speed_system(speed: Res<GameSpeed>, mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(speed.factor());
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
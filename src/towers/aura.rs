@@ -0,0 +1,176 @@
+//! Support towers
+
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::{
+    arena::grid::GridResource, mob::Camouflage, prelude::*, weapons::weapon::ProjectileData,
+};
+
+use super::{TowerComponents, TowerPosition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AuraKind {
+    Damage,
+    Range,
+    Reload,
+    /// Reveals camouflaged enemies instead of buffing towers.
+    Radar,
+}
+
+/// A buff a support tower gives everything within `radius` tiles.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub(crate) struct Aura {
+    pub(crate) kind: AuraKind,
+    pub(crate) radius: u32,
+    /// Fraction added to the buffed stat.
+    pub(crate) strength: f32,
+}
+
+impl Aura {
+    /// Whether `tile` is within `radius` tiles of any tile of the support tower.
+    pub(crate) fn covers(&self, aura_tiles: &[TilePos], tile: &TilePos) -> bool {
+        aura_tiles
+            .iter()
+            .any(|center| center.x.abs_diff(tile.x).max(center.y.abs_diff(tile.y)) <= self.radius)
+    }
+
+    /// Side length in tiles of the square covered around a tower of `footprint` tiles.
+    pub(crate) fn side(&self, footprint: u32) -> u32 {
+        self.radius * 2 + footprint
+    }
+}
+
+impl std::fmt::Display for Aura {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            AuraKind::Damage => write!(f, "+{:.0}% damage", self.strength * 100.0),
+            AuraKind::Range => write!(f, "+{:.0}% range", self.strength * 100.0),
+            AuraKind::Reload => write!(f, "+{:.0}% reload speed", self.strength * 100.0),
+            AuraKind::Radar => write!(f, "Reveals hidden enemies"),
+        }?;
+        write!(f, " within {} tiles", self.radius)
+    }
+}
+
+/// Multipliers from every aura covering a tower, applied on top of its weapon.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub(crate) struct TowerModifiers {
+    pub(crate) damage: f32,
+    pub(crate) range: f32,
+    pub(crate) reload: f32,
+}
+
+impl Default for TowerModifiers {
+    fn default() -> Self {
+        Self {
+            damage: 1.0,
+            range: 1.0,
+            reload: 1.0,
+        }
+    }
+}
+
+impl TowerModifiers {
+    fn add(&mut self, aura: &Aura) {
+        match aura.kind {
+            AuraKind::Damage => self.damage += aura.strength,
+            AuraKind::Range => self.range += aura.strength,
+            AuraKind::Reload => self.reload += aura.strength,
+            AuraKind::Radar => {}
+        }
+    }
+
+    pub(crate) fn apply(&self, projectile_data: &mut ProjectileData) {
+        projectile_data.damage = (projectile_data.damage as f32 * self.damage) as usize;
+    }
+
+    pub(crate) fn is_buffed(&self) -> bool {
+        *self != TowerModifiers::default()
+    }
+}
+
+impl std::fmt::Display for TowerModifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Aura: x{:.2} damage, x{:.2} range, x{:.2} reload speed",
+            self.damage, self.range, self.reload
+        )
+    }
+}
+
+/// Every aura with the tiles of the tower giving it.
+fn aura_tiles<'a>(
+    grid: &GridResource,
+    auras: &'a Query<(&Aura, &TowerComponents, &TowerPosition)>,
+) -> Vec<(&'a Aura, Vec<TilePos>)> {
+    auras
+        .iter()
+        .filter_map(|(aura, tower, position)| {
+            grid.footprint(position, tower.tower.footprint())
+                .map(|tiles| (aura, tiles))
+        })
+        .collect()
+}
+
+pub(crate) fn aura_system(
+    grid: Res<GridResource>,
+    auras: Query<(&Aura, &TowerComponents, &TowerPosition)>,
+    mut towers: Query<(&TowerComponents, &TowerPosition, &mut TowerModifiers)>,
+) {
+    let auras = aura_tiles(&grid, &auras);
+    for (tower, position, mut modifiers) in towers.iter_mut() {
+        let Some(tiles) = grid.footprint(position, tower.tower.footprint()) else {
+            continue;
+        };
+        let mut next = TowerModifiers::default();
+        for (aura, aura_tiles) in auras.iter() {
+            // Large towers are buffed when any of their tiles is covered
+            if tiles.iter().any(|tile| aura.covers(aura_tiles, tile)) {
+                next.add(aura);
+            }
+        }
+        if *modifiers != next {
+            *modifiers = next;
+        }
+    }
+}
+
+pub(crate) fn radar_system(
+    grid: Res<GridResource>,
+    radars: Query<(&Aura, &TowerComponents, &TowerPosition)>,
+    mut hidden: Query<(&TilePos, &mut Camouflage)>,
+) {
+    let radars = aura_tiles(&grid, &radars);
+    for (tile, mut camouflage) in hidden.iter_mut() {
+        let revealed = radars.iter().any(|(aura, aura_tiles)| {
+            aura.kind == AuraKind::Radar && aura.covers(aura_tiles, tile)
+        });
+        if camouflage.revealed != revealed {
+            camouflage.revealed = revealed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::towers::TowerTypes;
+
+    use super::*;
+
+    #[test]
+    fn test_covers_whole_footprint() {
+        let radar = TowerTypes::Radar.aura().unwrap();
+        let tiles = [(5, 5), (5, 6), (6, 5), (6, 6)].map(|(x, y)| TilePos::new(x, y));
+        // The radius counts from every edge of a large tower alike
+        let reach = 6 + radar.radius;
+        assert!(radar.covers(&tiles, &TilePos::new(reach, reach)));
+        assert!(radar.covers(&tiles, &TilePos::new(5 - radar.radius, 5 - radar.radius)));
+        assert!(!radar.covers(&tiles, &TilePos::new(reach + 1, 5)));
+        assert!(!radar.covers(&tiles, &TilePos::new(5 - radar.radius - 1, 5)));
+        assert_eq!(
+            radar.side(TowerTypes::Radar.footprint()),
+            radar.radius * 2 + 2
+        );
+    }
+}
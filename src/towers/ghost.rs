@@ -0,0 +1,119 @@
+//! Preview of a tower before it is built
+
+use crate::{
+    arena::{
+        grid::{GridResource, HoveredSpot},
+        GRID_SQUARE_SIZE,
+    },
+    assets::SpriteAssets,
+    prelude::*,
+    state::{StateDespawnMarker, IN_GAME},
+    weapons::weapon::WeaponComponent,
+};
+
+use super::{check_placement, TowerGizmos, TowerInfo, TowerProgress, TowerTypes};
+
+/// The tower type selected for building, previewed on the hovered tile.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct GhostTower(pub(crate) Option<TowerTypes>);
+
+#[derive(Debug, Component)]
+pub(crate) struct TowerGhost;
+
+/// Build cost shown above the ghost.
+#[derive(Debug, Component)]
+pub(crate) struct GhostCost;
+
+fn ghost_color(valid: bool) -> Color {
+    if valid {
+        Color::rgba(0.4, 1.0, 0.4, 0.5)
+    } else {
+        Color::rgba(1.0, 0.3, 0.3, 0.5)
+    }
+}
+
+pub(crate) fn ghost_system(
+    mut commands: Commands,
+    ghost_tower: Res<GhostTower>,
+    hovered_spot: Res<HoveredSpot>,
+    grid: Res<GridResource>,
+    assets: Res<SpriteAssets>,
+    tower_info: Res<TowerInfo>,
+    mut ghosts: Query<(Entity, &mut Sprite, &mut Transform, &mut Handle<Image>), With<TowerGhost>>,
+    mut costs: Query<(&mut Text, &mut Transform), (With<GhostCost>, Without<TowerGhost>)>,
+    mut gizmos: Gizmos<TowerGizmos>,
+) {
+    let (Some(tower_type), Some((tile, pos))) = (ghost_tower.0, hovered_spot.0) else {
+        for (entity, ..) in ghosts.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+
+    let valid = check_placement(&grid, &tower_type, &pos).is_ok();
+    let color = ghost_color(valid);
+    let transform = tower_type
+        .placed_transform(&tile, &grid)
+        .with_scale(Vec3::splat(0.5));
+    let size = Vec2::splat(GRID_SQUARE_SIZE * tower_type.footprint() as f32);
+    let image = assets.tower_sprites[&tower_type].clone();
+    let cost = format!("{} Bricks", tower_info.get_cost(&tower_type));
+    // Just above the ghost, in the ghost's own scale
+    let cost_transform = Transform::from_xyz(0.0, size.y, 1.0);
+
+    if let Ok((_, mut sprite, mut ghost_transform, mut ghost_image)) = ghosts.get_single_mut() {
+        sprite.color = color;
+        sprite.custom_size = Some(size);
+        *ghost_transform = transform;
+        *ghost_image = image;
+        for (mut text, mut text_transform) in costs.iter_mut() {
+            text.sections[0].value = cost.clone();
+            *text_transform = cost_transform;
+        }
+    } else {
+        commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(size),
+                        ..Default::default()
+                    },
+                    transform,
+                    texture: image,
+                    ..Default::default()
+                },
+                TowerGhost,
+                StateDespawnMarker(IN_GAME),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            cost,
+                            TextStyle {
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            },
+                        ),
+                        transform: cost_transform,
+                        ..Default::default()
+                    },
+                    GhostCost,
+                ));
+            });
+    }
+
+    // Show what the tower would cover
+    let center = transform.translation.truncate();
+    if let Some(weapon_type) = tower_type.weapon() {
+        let range =
+            WeaponComponent::from(weapon_type).range(&grid, TowerProgress::default().get_level());
+        gizmos.circle_2d(center, range, color).segments(64);
+    }
+    if let Some(aura) = tower_type.aura() {
+        let side = aura.side(tower_type.footprint()) as f32 * grid.grid_square_size;
+        gizmos.rect_2d(center, 0.0, Vec2::splat(side), color);
+    }
+}
@@ -1,13 +1,15 @@
 //! Towers module.
 
+use bevy::gizmos::GizmoPlugin;
 use bevy::utils::info;
 use bevy_ecs_tilemap::tiles::TilePos;
 use enum_iterator::all;
 use enum_iterator::Sequence;
-use rand::thread_rng;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::f32::consts::{FRAC_PI_3, FRAC_PI_4};
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::hash::Hash;
@@ -16,24 +18,41 @@ use crate::arena::grid::GridResource;
 use crate::arena::grid::HighlightedSpot;
 use crate::arena::path_finding;
 use crate::arena::GRID_SQUARE_SIZE;
-use crate::collision::CollisionTypes;
 use crate::collision::GameLayer;
+use crate::mob::Camouflage;
 use crate::mob::EnemyUnit;
 use crate::player::Player;
 use crate::player::PlayerUpdateEvent;
-use crate::towers::path_finding::path_finding;
+use crate::rng::GameRng;
+use crate::state::{AppState, GameplaySet, RenderSet, StateDespawnMarker, IN_GAME};
+use crate::towers::path_finding::path_finding_blocked;
+use crate::ui::level::MapLevel;
 use crate::weapons::weapon::ProjectileData;
 use crate::weapons::weapon::WeaponComponent;
 use crate::weapons::weapon::WeaponTypes;
 use crate::weapons::weapon::WeaponUpdate;
 use crate::weapons::FireWeaponEvent;
 use crate::{arena::grid::GridClickEvent, assets::SpriteAssets, prelude::*};
+
+use self::aura::{aura_system, radar_system, Aura, AuraKind, TowerModifiers};
+use self::ghost::{ghost_system, GhostTower};
+use self::synergy::{synergy_system, TowerSynergies};
+use self::traps::{trap_click_system, trap_trigger_system, TrapTriggerEvent};
+use self::turret::{turret_rotation_system, Turret};
+use self::upgrades::TowerUpgrades;
+
+pub(crate) mod aura;
+pub(crate) mod ghost;
+pub(crate) mod synergy;
+pub(crate) mod traps;
+pub(crate) mod turret;
+pub(crate) mod upgrades;
 #[derive(Debug, Copy, Clone)]
 pub(crate) enum TowerLevelUpReason {
     Kill,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Copy, Serialize, Deserialize)]
 pub(crate) struct TowerDamage(u128);
 
 impl TowerDamage {
@@ -63,46 +82,74 @@ impl Display for TowerDamage {
     }
 }
 
-#[derive(Debug, Component, Clone, PartialEq, Eq, Default, Copy)]
+#[derive(Debug, Component, Clone, PartialEq, Eq, Default, Copy, Serialize, Deserialize)]
 pub(crate) struct TowerStatusEffects {
     pub(crate) potency: u32,
     pub(crate) duration: u32,
 }
 
-#[derive(Debug, Component, Clone, PartialEq)]
-pub(crate) struct TowerData {
-    pub(crate) experience: u32,
+/// Progression of a single tower, earned through its own kills.
+#[derive(Debug, Component, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TowerProgress {
+    experience: u32,
     level: u32,
+    kills: u32,
     status_effect: TowerStatusEffects,
-    current_cost: u32,
-    tower_count: u32,
-    tower_type: TowerTypes,
-    damage_done: TowerDamage,
+    /// Damage this tower's hits and effects took off enemies.
+    damage: TowerDamage,
 }
 
-impl TowerData {
-    pub(crate) fn new(tower_type: &TowerTypes) -> Self {
+impl Default for TowerProgress {
+    fn default() -> Self {
         Self {
             experience: 0,
             level: 1,
+            kills: 0,
             status_effect: TowerStatusEffects::default(),
-            current_cost: 1,
-            tower_count: 0,
-            tower_type: *tower_type,
-            damage_done: TowerDamage::default(),
+            damage: TowerDamage::default(),
         }
     }
+}
 
+impl TowerProgress {
     pub(crate) fn get_level(&self) -> u32 {
         self.level
     }
-    pub(crate) fn add_experience(&mut self, experience: u32) {
+
+    pub(crate) fn get_experience(&self) -> u32 {
+        self.experience
+    }
+
+    pub(crate) fn experience_needed(&self) -> u32 {
+        self.level * 333
+    }
+
+    pub(crate) fn get_kills(&self) -> u32 {
+        self.kills
+    }
+
+    pub(crate) fn add_kill(&mut self) {
+        self.kills = self.kills.saturating_add(1);
+    }
+
+    pub(crate) fn get_damage(&self) -> TowerDamage {
+        self.damage
+    }
+
+    pub(crate) fn add_damage(&mut self, damage: u32) {
+        self.damage.add_damage(damage);
+    }
+
+    /// Returns the number of levels gained.
+    pub(crate) fn add_experience(&mut self, experience: u32) -> u32 {
+        let previous_level = self.level;
         self.experience += experience;
-        while self.experience > self.level * 333 {
+        while self.experience > self.experience_needed() {
             self.experience -= self.level;
             self.level += 1;
             self.mutate_status()
         }
+        self.level - previous_level
     }
 
     fn mutate_status(&mut self) {
@@ -113,6 +160,85 @@ impl TowerData {
     pub(crate) fn get_status(&self) -> &TowerStatusEffects {
         &self.status_effect
     }
+}
+
+/// Bricks spent on a single tower, refunded when it is sold.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TowerSpending {
+    spent: u32,
+    built_wave: u32,
+}
+
+impl TowerSpending {
+    pub(crate) fn new(cost: u32, wave: u32) -> Self {
+        Self {
+            spent: cost,
+            built_wave: wave,
+        }
+    }
+
+    pub(crate) fn spend(&mut self, bricks: u32) {
+        self.spent = self.spent.saturating_add(bricks);
+    }
+
+    pub(crate) fn get_spent(&self) -> u32 {
+        self.spent
+    }
+
+    /// Bricks returned when selling, in full if the tower was built this wave.
+    pub(crate) fn refund(&self, wave: u32, sell_refund: &SellRefund) -> u32 {
+        if wave == self.built_wave {
+            self.spent
+        } else {
+            self.spent * sell_refund.percent.min(100) / 100
+        }
+    }
+}
+
+/// Percentage of the bricks spent on a tower returned when it is sold.
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct SellRefund {
+    pub(crate) percent: u32,
+}
+
+impl Default for SellRefund {
+    fn default() -> Self {
+        Self { percent: 70 }
+    }
+}
+
+/// Data shared by every tower of one type: build cost, damage dealt and research.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TowerData {
+    research: u32,
+    current_cost: u32,
+    tower_count: u32,
+    tower_type: TowerTypes,
+    damage_done: TowerDamage,
+}
+
+impl TowerData {
+    pub(crate) fn new(tower_type: &TowerTypes) -> Self {
+        Self {
+            research: 0,
+            current_cost: 1,
+            tower_count: 0,
+            tower_type: *tower_type,
+            damage_done: TowerDamage::default(),
+        }
+    }
+
+    pub(crate) fn get_research(&self) -> u32 {
+        self.research
+    }
+
+    pub(crate) fn research_cost(&self) -> u32 {
+        self.tower_type.cost(1) * 10 * (self.research + 1)
+    }
+
+    pub(crate) fn research(&mut self) {
+        self.research += 1;
+    }
 
     pub(crate) fn build_tower(&mut self) {
         self.tower_count += 1;
@@ -124,12 +250,6 @@ impl TowerData {
         self.current_cost = self.tower_type.cost(self.tower_count);
     }
 
-    pub(crate) fn upgrade(&mut self) {
-        // Get experience needed for next level
-        let experience_needed = self.level * 333;
-        self.add_experience(experience_needed);
-    }
-
     pub(crate) fn add_damage(&mut self, damage: u32) {
         self.damage_done.add_damage(damage);
     }
@@ -139,38 +259,27 @@ impl TowerData {
     }
 }
 
-#[derive(Debug, Resource)]
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
 pub(crate) struct TowerInfo {
     pub(crate) tower_data: HashMap<TowerTypes, TowerData>,
 }
 
 impl TowerInfo {
-    fn next_level(&self, tower: &TowerTypes) -> u32 {
-        self.get_level(tower) + 1
-    }
-
-    pub(crate) fn get_level(&self, tower: &TowerTypes) -> u32 {
-        if let Some(data) = self.tower_data.get(tower) {
-            return data.get_level();
-        }
-        1
+    pub(crate) fn get_research(&self, tower: &TowerTypes) -> u32 {
+        self.tower_data
+            .get(tower)
+            .map(|data| data.get_research())
+            .unwrap_or_default()
     }
 
-    pub(crate) fn add_experience(&mut self, tower: &TowerTypes, experience: u32) -> u32 {
-        let previous_level = self.get_level(tower);
-        if let Some(data) = self.tower_data.get_mut(tower) {
-            data.add_experience(experience)
-        }
-        let current = self.get_level(tower);
-        if current < previous_level {
-            error!("Tower level decreased");
-        }
-        current.saturating_sub(previous_level)
+    pub(crate) fn research_cost(&self, tower: &TowerTypes) -> u32 {
+        self.get_data(tower).research_cost()
     }
 
-    pub(crate) fn upgrade(&mut self, tower: &TowerTypes) {
+    /// Type-wide research, applies to every tower of the type on top of its own level.
+    pub(crate) fn research(&mut self, tower: &TowerTypes) {
         if let Some(data) = self.tower_data.get_mut(tower) {
-            data.upgrade();
+            data.research();
         }
     }
 
@@ -204,9 +313,28 @@ impl TowerInfo {
         }
     }
 
-    pub(crate) fn mega_fire(&self, tower: &TowerTypes, projectile_data: &mut ProjectileData) {
-        let level = self.get_level(tower);
-        projectile_data.damage += level as usize;
+    pub(crate) fn mega_fire(
+        &self,
+        tower: &TowerTypes,
+        progress: &TowerProgress,
+        projectile_data: &mut ProjectileData,
+    ) {
+        let bonus = progress.get_level() + self.get_research(tower) * 2;
+        projectile_data.damage += bonus as usize;
+    }
+
+    /// Status effects of a tower, its own plus those from research.
+    pub(crate) fn status_effects(
+        &self,
+        tower: &TowerTypes,
+        progress: &TowerProgress,
+    ) -> TowerStatusEffects {
+        let research = self.get_research(tower);
+        let status = progress.get_status();
+        TowerStatusEffects {
+            potency: status.potency.saturating_add(research),
+            duration: status.duration,
+        }
     }
 
     pub(crate) fn add_damage(&mut self, tower: &TowerTypes, damage: u32) {
@@ -248,43 +376,191 @@ pub struct TowerPlugin;
 
 impl Plugin for TowerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, draw_tower_range_system);
-        app.add_systems(Update, tower_system)
+        // Headless runs have no gizmo renderer
+        if app.is_plugin_added::<GizmoPlugin>() {
+            app.init_gizmo_group::<TowerGizmos>();
+        }
+        app.add_systems(Update, draw_tower_range_system.in_set(RenderSet));
+        app.add_systems(FixedUpdate, tower_system.in_set(GameplaySet))
             .add_event::<TowerLevelUp>()
-            .init_gizmo_group::<TowerGizmos>()
             .insert_resource(TowerInfo::default())
-            .add_systems(Update, tower_fire_system)
-            .add_systems(Update, tower_level_up_system)
-            .add_systems(Update, update_tower_dps)
-            .add_systems(Update, tower_upgrade_system);
+            .init_resource::<SellRefund>()
+            .init_resource::<GhostTower>()
+            .add_systems(Update, ghost_system.in_set(RenderSet))
+            .add_systems(FixedUpdate, synergy_system.in_set(GameplaySet))
+            .add_systems(
+                FixedUpdate,
+                (tower_fire_system, turret_rotation_system)
+                    .chain()
+                    .in_set(GameplaySet),
+            )
+            .add_systems(FixedUpdate, tower_level_up_system.in_set(GameplaySet))
+            .add_systems(FixedUpdate, (aura_system, radar_system).in_set(GameplaySet))
+            .add_event::<TrapTriggerEvent>()
+            .add_systems(
+                FixedUpdate,
+                (trap_click_system, trap_trigger_system).in_set(GameplaySet),
+            )
+            .add_systems(FixedUpdate, tower_upgrade_system.in_set(GameplaySet))
+            .add_systems(OnEnter(AppState::GameCreate), reset_towers);
     }
 }
 
 #[derive(Debug, Component, Deref, DerefMut, Eq, PartialEq)]
 pub(crate) struct TowerPosition(pub(crate) TilePos);
 
+/// Why a tower can not be built on a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PlacementError {
+    OutOfBounds,
+    Occupied,
+    BlocksPath,
+}
+
+impl Display for PlacementError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlacementError::OutOfBounds => write!(f, "Tower does not fit on the grid"),
+            PlacementError::Occupied => write!(f, "Position is occupied"),
+            PlacementError::BlocksPath => write!(f, "Tower position will cause no path"),
+        }
+    }
+}
+
+/// The tiles a tower would cover with its bottom left corner on `pos`, if it can be built there.
+pub(crate) fn check_placement(
+    grid: &GridResource,
+    tower_type: &TowerTypes,
+    pos: &TilePos,
+) -> Result<Vec<TilePos>, PlacementError> {
+    let tiles = grid
+        .footprint(pos, tower_type.footprint())
+        .ok_or(PlacementError::OutOfBounds)?;
+    if grid.is_area_occupied(&tiles) || tiles.iter().any(|tile| grid.get_trap(*tile).is_some()) {
+        return Err(PlacementError::Occupied);
+    }
+    // Ensure tower position wont cause no path
+    if path_finding_blocked(grid, grid.grid_enemy_start, &tiles).is_none() {
+        return Err(PlacementError::BlocksPath);
+    }
+    Ok(tiles)
+}
+
+fn reset_towers(mut commands: Commands) {
+    commands.insert_resource(TowerInfo::default());
+    commands.insert_resource(GhostTower::default());
+}
+
 fn tower_upgrade_system(
     mut grid_event: EventReader<GridClickEvent>,
-    mut tower_query: Query<(&TowerComponents, &mut WeaponComponent)>,
+    mut tower_query: Query<(
+        &TowerComponents,
+        &mut WeaponComponent,
+        &mut TowerUpgrades,
+        &mut TowerSpending,
+    )>,
     mut player: Query<&mut Player>,
+    mut tower_info: ResMut<TowerInfo>,
+    grid: Res<GridResource>,
+    mut rng: ResMut<GameRng>,
 ) {
     for event in grid_event.read() {
         match event {
-            GridClickEvent::UpgradeTower(tower_type, _tile_pos) => {
-                for (tc, mut wc) in tower_query.iter_mut() {
-                    if &tc.tower == tower_type {
-                        if player.single_mut().remove_bricks(wc.cost()) {
-                            wc.level_up();
+            GridClickEvent::UpgradeTower(_tower_type, tile_pos) => {
+                let Some(entity) = grid.get_tower(*tile_pos) else {
+                    continue;
+                };
+                if let Ok((_, mut wc, _, mut spending)) = tower_query.get_mut(entity) {
+                    let cost = wc.cost();
+                    if player.single_mut().remove_bricks(cost) {
+                        wc.level_up(&mut **rng);
+                        spending.spend(cost);
+                    }
+                }
+            }
+            GridClickEvent::UpgradeBranch(tile_pos, branch) => {
+                let Some(entity) = grid.get_tower(*tile_pos) else {
+                    continue;
+                };
+                if let Ok((tc, mut wc, mut upgrades, mut spending)) = tower_query.get_mut(entity) {
+                    let Some(node) = upgrades.next(tc.tower.upgrade_tree(), *branch) else {
+                        trace!("Upgrade branch is locked or complete");
+                        continue;
+                    };
+                    if player.single_mut().remove_bricks(node.cost) {
+                        for effect in node.effects {
+                            wc.apply_upgrade(effect);
                         }
+                        upgrades.purchase(*branch);
+                        spending.spend(node.cost);
                     }
                 }
             }
+            GridClickEvent::ResearchTower(tower_type) => {
+                let cost = tower_info.research_cost(tower_type);
+                if player.single_mut().remove_bricks(cost) {
+                    tower_info.research(tower_type);
+                }
+            }
 
             _ => {}
         }
     }
 }
 
+/// Spawns a fresh tower on `pos`, the caller marks its tiles occupied.
+pub(crate) fn spawn_tower(
+    commands: &mut Commands,
+    assets: &SpriteAssets,
+    grid: &GridResource,
+    tower_type: &TowerTypes,
+    tile: &Transform,
+    pos: TilePos,
+    spending: TowerSpending,
+) -> Entity {
+    let size = tower_type.footprint();
+    let image = assets.tower_sprites[tower_type].clone();
+    let tower_component = TowerComponents { tower: *tower_type };
+
+    let transform = tower_type.placed_transform(tile, grid);
+
+    let mut tower = commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: tower_type.tint(),
+                custom_size: Some(Vec2::splat(GRID_SQUARE_SIZE * size as f32)),
+                ..Default::default()
+            },
+            transform: transform.with_scale(Vec3::splat(0.5)),
+            texture: image.clone(),
+
+            ..Default::default()
+        },
+        TowerPosition(pos),
+        tower_component,
+        TowerProgress::default(),
+        TowerUpgrades::default(),
+        spending,
+        TowerSynergies::default(),
+        StateDespawnMarker(IN_GAME),
+        CollisionLayers::new(GameLayer::Tower, [GameLayer::Enemy]),
+    ));
+    if let Some(weapon_type) = tower_type.weapon() {
+        // Turrets rest facing where the enemies come from
+        let spawn = path_finding::to_position(grid.grid_enemy_start, grid).0;
+        let rest = spawn - transform.translation.truncate();
+        tower.insert((
+            WeaponComponent::from(weapon_type),
+            TowerModifiers::default(),
+            tower_type.turret(rest.y.atan2(rest.x)),
+        ));
+    }
+    if let Some(aura) = tower_type.aura() {
+        tower.insert(aura);
+    }
+    tower.id()
+}
+
 fn tower_system(
     mut commands: Commands,
     mut grid_event: EventReader<GridClickEvent>,
@@ -293,6 +569,9 @@ fn tower_system(
     mut grid: ResMut<GridResource>,
     mut tower_info: ResMut<TowerInfo>,
     player: Query<&Player>,
+    towers: Query<(&TowerPosition, &TowerSpending)>,
+    map_level: Res<MapLevel>,
+    sell_refund: Res<SellRefund>,
 ) {
     for event in grid_event.read() {
         match event {
@@ -303,50 +582,40 @@ fn tower_system(
                     trace!("Not enough bricks");
                     continue;
                 }
-                if grid.is_occupied(pos) {
-                    trace!("Position is occupied");
-                    continue;
-                }
-
-                // Ensure tower position wont cause no path
-                grid.set_occupied(pos, Entity::PLACEHOLDER);
-                if path_finding(&grid, grid.grid_enemy_start).is_none() {
-                    trace!("Tower position will cause no path");
-                    grid.remove_occupied(pos);
-                    continue;
+                let tiles = match check_placement(&grid, tower_type, pos) {
+                    Ok(tiles) => tiles,
+                    Err(error) => {
+                        trace!("{}", error);
+                        continue;
+                    }
                 };
-                let image = assets.tower_sprites[tower_type].clone();
-                let tower_component = TowerComponents { tower: *tower_type };
-
-                let weapon_component: WeaponComponent = WeaponComponent::from(*tower_type);
-
-                let entity = commands
-                    .spawn((
-                        SpriteBundle {
-                            sprite: Sprite {
-                                custom_size: Some(Vec2::splat(GRID_SQUARE_SIZE)),
-                                ..Default::default()
-                            },
-                            transform: transform.with_scale(Vec3::splat(0.5)),
-                            texture: image.clone(),
-
-                            ..Default::default()
-                        },
-                        TowerPosition(*pos),
-                        tower_component,
-                        weapon_component,
-                        CollisionLayers::new(GameLayer::Tower, [GameLayer::Enemy]),
-                    ))
-                    .id();
-
-                grid.set_occupied(pos, entity);
+                let spending = TowerSpending::new(bricks, map_level.level);
+                let entity = spawn_tower(
+                    &mut commands,
+                    &assets,
+                    &grid,
+                    tower_type,
+                    transform,
+                    *pos,
+                    spending,
+                );
+
+                grid.set_area_occupied(&tiles, entity);
                 tower_info.build_tower(tower_type);
                 player_event.send(PlayerUpdateEvent::Build(bricks));
             }
             GridClickEvent::RemoveTower(tower_type, tile_pos) => {
                 if let Some(entity) = grid.get_tower(*tile_pos) {
+                    let Ok((position, spending)) = towers.get(entity) else {
+                        continue;
+                    };
+                    let refund = spending.refund(map_level.level, &sell_refund);
+                    player_event.send(PlayerUpdateEvent::Refund(refund));
                     commands.entity(entity).despawn();
-                    grid.remove_occupied(tile_pos);
+                    // Free every tile of the footprint, not just the one clicked
+                    if let Some(tiles) = grid.footprint(position, tower_type.footprint()) {
+                        grid.remove_area_occupied(&tiles);
+                    }
                     tower_info.remove_tower(tower_type);
                 }
             }
@@ -358,57 +627,46 @@ fn tower_system(
 
 fn draw_tower_range_system(
     tower_query: Query<(
-        &TowerComponents,
+        &TowerProgress,
         &WeaponComponent,
+        &TowerModifiers,
+        &Turret,
         &Transform,
-        &TowerPosition,
     )>,
-    tower_info: Res<TowerInfo>,
+    aura_query: Query<(&Aura, &TowerComponents, &Transform)>,
     grid: Res<GridResource>,
     mut my_gizmos: Gizmos<TowerGizmos>,
     highlighted_spot: Res<HighlightedSpot>,
 ) {
-    if let Some((_, _, tile_pos)) = highlighted_spot.0 {
-        for (tower, weapon, transform, tower_position) in tower_query.iter() {
-            if tower_position.0 != tile_pos {
-                continue;
-            }
-            let tower_position = transform.translation;
-            let range = weapon
-                .weapon_type()
-                .range(&grid, tower_info.get_level(&tower.tower));
-
-            my_gizmos
-                .circle_2d(tower_position.truncate(), range, Color::NAVY)
-                .segments(64);
+    let Some((_, _, tile_pos)) = highlighted_spot.0 else {
+        return;
+    };
+    // Any tile of a large tower selects it
+    let Some(selected) = grid.get_tower(tile_pos) else {
+        return;
+    };
+    if let Ok((progress, weapon, modifiers, turret, transform)) = tower_query.get(selected) {
+        let tower_position = transform.translation;
+        let range = weapon.range(&grid, progress.get_level()) * modifiers.range;
+
+        my_gizmos
+            .circle_2d(tower_position.truncate(), range, Color::NAVY)
+            .segments(64);
+        if let Some((left, right)) = turret.arc_edges() {
+            let center = tower_position.truncate();
+            my_gizmos.line_2d(center, center + left * range, Color::NAVY);
+            my_gizmos.line_2d(center, center + right * range, Color::NAVY);
         }
     }
-}
-
-fn update_tower_dps(
-    mut tower_info: ResMut<TowerInfo>,
-    mut collision_event_reader: EventReader<CollisionTypes>,
-    tower_query: Query<(Entity, &TowerComponents, &TowerPosition)>,
-) {
-    for event in collision_event_reader.read() {
-        match event {
-            CollisionTypes::ProjectileToEnemy {
-                projectile_data,
-                tile,
-                projectile_entity,
-                ..
-            } => {
-                for (entity, tower, tower_pos) in tower_query.iter() {
-                    if entity
-                        == projectile_data
-                            .source_entity
-                            .unwrap_or_else(|| Entity::PLACEHOLDER)
-                    {
-                        tower_info.add_damage(&tower.tower, projectile_data.damage as u32);
-                    }
-                }
-            }
-        }
+    // Auras cover a square of tiles around the tower
+    if let Ok((aura, tower, transform)) = aura_query.get(selected) {
+        let side = aura.side(tower.tower.footprint()) as f32 * grid.grid_square_size;
+        my_gizmos.rect_2d(
+            transform.translation.truncate(),
+            0.0,
+            Vec2::splat(side),
+            Color::GOLD,
+        );
     }
 }
 
@@ -417,55 +675,89 @@ fn tower_fire_system(
     mut tower_query: Query<(
         Entity,
         &mut TowerComponents,
+        &TowerProgress,
         &mut WeaponComponent,
+        &TowerModifiers,
+        &mut Turret,
+        &TowerSynergies,
         &Transform,
     )>,
-    enemies_position: Query<(Entity, &Transform), With<EnemyUnit>>,
+    partner_query: Query<&Transform, With<TowerComponents>>,
+    enemies_position: Query<(Entity, &Transform, Option<&Camouflage>), With<EnemyUnit>>,
     mut fire_event_writer: EventWriter<FireWeaponEvent>,
     tower_info: Res<TowerInfo>,
     grid: Res<GridResource>,
+    mut rng: ResMut<GameRng>,
 ) {
-    for (entity, tower, mut weapon, transform) in tower_query.iter_mut() {
-        weapon.update(time.delta());
-        if let Some(mut projectile_data) = weapon.fire() {
-            // Check how many projectiles to fire
-            let mut enemies_to_target = Vec::new();
-            let mut enemies_targeted = HashSet::new();
-            for _ in 0..projectile_data.count {
-                let tower_position = transform.translation;
-                let mut nearest_distance = f32::MAX;
-                let futhest_disance = weapon
-                    .weapon_type()
-                    .range(&grid, tower_info.get_level(&tower.tower));
-
-                for (entity, enemy_transform) in enemies_position.iter() {
-                    if enemies_to_target.len() == projectile_data.count {
-                        break;
-                    }
-                    if enemies_targeted.contains(&entity) {
-                        continue;
-                    }
-                    let enemy_position = enemy_transform.translation;
-                    let distance = tower_position.distance(enemy_position);
+    for (entity, tower, progress, mut weapon, modifiers, mut turret, synergies, transform) in
+        tower_query.iter_mut()
+    {
+        weapon.update(time.delta().mul_f32(modifiers.reload));
+
+        // Partners spot for each other
+        let partners = synergies
+            .partners()
+            .filter_map(|partner| partner_query.get(partner).ok())
+            .map(|partner| partner.translation)
+            .collect::<Vec<_>>();
+
+        // Check how many projectiles to fire
+        let count = weapon.projectile_data.count;
+        let mut enemies_to_target = Vec::new();
+        let mut enemies_targeted = HashSet::new();
+        for _ in 0..count {
+            let tower_position = transform.translation;
+            let mut nearest_distance = f32::MAX;
+            let futhest_disance = weapon.range(&grid, progress.get_level()) * modifiers.range;
 
-                    if distance < nearest_distance && distance < futhest_disance {
-                        nearest_distance = distance;
-                        enemies_to_target.push(enemy_position);
-                        enemies_targeted.insert(entity);
-                    }
+            for (entity, enemy_transform, camouflage) in enemies_position.iter() {
+                if enemies_to_target.len() == count {
+                    break;
+                }
+                if enemies_targeted.contains(&entity) || !Camouflage::targetable(camouflage) {
+                    continue;
+                }
+                let enemy_position = enemy_transform.translation;
+                let distance = tower_position.distance(enemy_position);
+
+                let in_reach = distance < futhest_disance
+                    || partners
+                        .iter()
+                        .any(|partner| partner.distance(enemy_position) < futhest_disance);
+
+                if distance < nearest_distance
+                    && in_reach
+                    && turret.in_arc((enemy_position - tower_position).truncate())
+                {
+                    nearest_distance = distance;
+                    enemies_to_target.push(enemy_position);
+                    enemies_targeted.insert(entity);
                 }
             }
-            // Find the nearest enemy
-            let tower_position = transform.translation;
+        }
+        let tower_position = transform.translation;
+
+        // Turn towards the nearest enemy and only fire once on target
+        let Some(nearest) = enemies_to_target.last() else {
+            continue;
+        };
+        let aim = (*nearest - tower_position).truncate();
+        if !turret.turn_towards(aim, time.delta_seconds()) {
+            continue;
+        }
 
+        if let Some(mut projectile_data) = weapon.fire() {
             for nearest_enemy_position in enemies_to_target {
                 let direction = (nearest_enemy_position - tower_position).normalize();
                 let velocity = (direction * projectile_data.speed_multiplier).truncate(); // Set the speed as needed
                 projectile_data.source_entity = Some(entity);
-                tower_info.mega_fire(&tower.tower, &mut projectile_data);
+                tower_info.mega_fire(&tower.tower, progress, &mut projectile_data);
+                let mut shot = projectile_data;
+                modifiers.apply(&mut shot);
+                shot.roll(&mut **rng);
 
                 fire_event_writer.send(FireWeaponEvent {
-                    weapon_projectile_data: projectile_data,
+                    weapon_projectile_data: shot,
                     source_transform: *transform,
                     velocity: LinearVelocity(velocity),
                     source_entity: entity,
@@ -477,8 +769,8 @@ fn tower_fire_system(
 
 fn tower_level_up_system(
     mut tower_level_up_event: EventReader<TowerLevelUp>,
-    mut tower_query: Query<(Entity, &mut TowerComponents, &mut WeaponComponent)>,
-    mut tower_datum: ResMut<TowerInfo>,
+    mut tower_query: Query<(&mut TowerProgress, &mut WeaponComponent)>,
+    mut rng: ResMut<GameRng>,
 ) {
     for event in tower_level_up_event.read() {
         match event {
@@ -487,10 +779,11 @@ fn tower_level_up_system(
                 reason: TowerLevelUpReason::Kill,
                 enemy_experience,
             } => {
-                if let Some((entity, tc, mut wc)) = tower_query.get_mut(event.entity).ok() {
-                    tower_datum.add_experience(&tc.tower, *enemy_experience as u32);
-                    if thread_rng().gen_bool(1.0 / 5_0000.0) {
-                        wc.level_up();
+                if let Some((mut progress, mut wc)) = tower_query.get_mut(*entity).ok() {
+                    progress.add_kill();
+                    progress.add_experience(*enemy_experience as u32);
+                    if rng.gen_bool(1.0 / 5_0000.0) {
+                        wc.level_up(&mut **rng);
                     }
                 }
             }
@@ -498,12 +791,29 @@ fn tower_level_up_system(
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Sequence, Copy, Actionlike, Reflect)]
-pub(crate) enum TowerTypes {
+/// Tower kinds.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, Sequence, Copy, Actionlike, Reflect, Serialize, Deserialize,
+)]
+pub enum TowerTypes {
+    /// Fires lasers.
     Basic,
+    /// Burns what it hits.
     Fire,
+    /// Slows what it hits.
     Ice,
+    /// Long range rifle.
     Sniper,
+    /// Aura boosting damage.
+    Amplifier,
+    /// Aura boosting range.
+    Scope,
+    /// Aura boosting reload speed.
+    Overclock,
+    /// Reveals camouflaged enemies.
+    Radar,
+    /// Blocks the path and nothing else.
+    Wall,
 }
 impl Display for TowerTypes {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -525,6 +835,89 @@ impl TowerTypes {
         sprite_assets
             .tower_sprites
             .insert(TowerTypes::Sniper, asset_server.load("sniper_tower.png"));
+        // Aura towers share the basic tower sprite and are told apart by their tint
+        for support in [
+            TowerTypes::Amplifier,
+            TowerTypes::Scope,
+            TowerTypes::Overclock,
+        ] {
+            sprite_assets
+                .tower_sprites
+                .insert(support, asset_server.load("basic_tower.png"));
+        }
+        sprite_assets
+            .tower_sprites
+            .insert(TowerTypes::Radar, asset_server.load("link.png"));
+        sprite_assets
+            .tower_sprites
+            .insert(TowerTypes::Wall, asset_server.load("bw-tile-square.png"));
+    }
+
+    /// Tint for towers sharing a sprite.
+    pub(crate) fn tint(&self) -> Color {
+        match self {
+            TowerTypes::Amplifier => Color::ORANGE,
+            TowerTypes::Scope => Color::CYAN,
+            TowerTypes::Overclock => Color::YELLOW,
+            TowerTypes::Wall => Color::GRAY,
+            _ => Color::WHITE,
+        }
+    }
+
+    /// The weapon the tower fires, support towers have none.
+    pub(crate) fn weapon(&self) -> Option<WeaponTypes> {
+        match self {
+            TowerTypes::Basic => Some(WeaponTypes::Laser),
+            TowerTypes::Fire => Some(WeaponTypes::Fire),
+            TowerTypes::Ice => Some(WeaponTypes::Ice),
+            TowerTypes::Sniper => Some(WeaponTypes::Rifle),
+            TowerTypes::Amplifier
+            | TowerTypes::Scope
+            | TowerTypes::Overclock
+            | TowerTypes::Radar
+            | TowerTypes::Wall => None,
+        }
+    }
+
+    /// Where the tower sits when built on `tile`, large towers are centred on their footprint.
+    pub(crate) fn placed_transform(&self, tile: &Transform, grid: &GridResource) -> Transform {
+        let offset = (self.footprint() - 1) as f32 * grid.grid_square_size / 2.0;
+        tile.with_translation(tile.translation + Vec3::new(offset, offset, 0.0))
+    }
+
+    /// Side length in tiles of the square the tower covers.
+    pub(crate) fn footprint(&self) -> u32 {
+        match self {
+            TowerTypes::Sniper | TowerTypes::Radar => 2,
+            _ => 1,
+        }
+    }
+
+    /// Turn rate in radians per second and the half width of the fire arc, if limited.
+    pub(crate) fn turret(&self, rest: f32) -> Turret {
+        let (turn_rate, arc) = match self {
+            TowerTypes::Basic => (4.0, None),
+            TowerTypes::Fire => (2.5, Some(FRAC_PI_3)),
+            TowerTypes::Ice => (3.0, None),
+            TowerTypes::Sniper => (1.5, Some(FRAC_PI_4)),
+            _ => (0.0, None),
+        };
+        Turret::new(rest, turn_rate, arc)
+    }
+
+    pub(crate) fn aura(&self) -> Option<Aura> {
+        let (kind, radius, strength) = match self {
+            TowerTypes::Amplifier => (AuraKind::Damage, 2, 0.25),
+            TowerTypes::Scope => (AuraKind::Range, 2, 0.2),
+            TowerTypes::Overclock => (AuraKind::Reload, 1, 0.3),
+            TowerTypes::Radar => (AuraKind::Radar, 4, 0.0),
+            _ => return None,
+        };
+        Some(Aura {
+            kind,
+            radius,
+            strength,
+        })
     }
 
     pub(crate) fn cost(&self, scale: u32) -> u32 {
@@ -533,6 +926,11 @@ impl TowerTypes {
             TowerTypes::Fire => 2 + scale * 5,
             TowerTypes::Ice => 3 * scale,
             TowerTypes::Sniper => 15 * scale,
+            TowerTypes::Amplifier => 20 + scale * 10,
+            TowerTypes::Scope => 15 + scale * 8,
+            TowerTypes::Overclock => 20 + scale * 10,
+            TowerTypes::Radar => 10 + scale * 5,
+            TowerTypes::Wall => 1 + scale / 10,
         }
     }
 }
@@ -542,27 +940,20 @@ pub(crate) struct TowerComponents {
     pub(crate) tower: TowerTypes,
 }
 
-impl From<TowerTypes> for WeaponTypes {
-    fn from(value: TowerTypes) -> Self {
-        match value {
-            TowerTypes::Basic => WeaponTypes::Laser,
-            TowerTypes::Fire => WeaponTypes::Fire,
-            TowerTypes::Ice => WeaponTypes::Ice,
-            TowerTypes::Sniper => WeaponTypes::Rifle,
-        }
-    }
-}
-
-impl From<TowerTypes> for ProjectileData {
-    fn from(value: TowerTypes) -> Self {
-        let weapon: WeaponTypes = value.into();
-        weapon.into()
-    }
-}
-
-impl From<TowerTypes> for WeaponComponent {
-    fn from(value: TowerTypes) -> Self {
-        let weapon_type: WeaponTypes = value.into();
-        weapon_type.into()
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_refund() {
+        let mut spending = TowerSpending::new(10, 3);
+        spending.spend(10);
+        let sell_refund = SellRefund::default();
+        assert_eq!(spending.refund(3, &sell_refund), 20);
+        assert_eq!(spending.refund(4, &sell_refund), 14);
+
+        // More than everything spent is never returned
+        let sell_refund = SellRefund { percent: 150 };
+        assert_eq!(spending.refund(4, &sell_refund), 20);
     }
 }
@@ -0,0 +1,153 @@
+//! Combos between neighbouring towers
+
+use std::fmt::Display;
+
+use crate::{
+    arena::grid::GridResource,
+    mob::{EffectType, EnemyUnit},
+    prelude::*,
+};
+
+use super::{TowerComponents, TowerPosition, TowerTypes};
+
+/// Bonus damage multiplier of a shatter hit.
+const SHATTER_MULTIPLIER: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Synergy {
+    /// Fire next to Ice deals bonus damage to slowed or frozen enemies.
+    Shatter,
+    /// Snipers side by side may fire at anything the other can reach.
+    SharedTargets(Entity),
+}
+
+impl Display for Synergy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Synergy::Shatter => write!(
+                f,
+                "Shatter: x{} damage to slowed enemies",
+                SHATTER_MULTIPLIER
+            ),
+            Synergy::SharedTargets(_) => {
+                write!(f, "Shared Targets: fires at its partner's targets")
+            }
+        }
+    }
+}
+
+impl Synergy {
+    /// The synergy a tower of type `tower` gets from a neighbour of type `neighbour`.
+    fn between(tower: TowerTypes, neighbour: TowerTypes, neighbour_entity: Entity) -> Option<Self> {
+        match (tower, neighbour) {
+            (TowerTypes::Fire, TowerTypes::Ice) => Some(Synergy::Shatter),
+            (TowerTypes::Sniper, TowerTypes::Sniper) => {
+                Some(Synergy::SharedTargets(neighbour_entity))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Synergies a tower has with its current neighbours.
+#[derive(Debug, Component, Clone, Default, PartialEq)]
+pub(crate) struct TowerSynergies(pub(crate) Vec<Synergy>);
+
+impl TowerSynergies {
+    /// Towers whose targets this one shares.
+    pub(crate) fn partners(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().filter_map(|synergy| match synergy {
+            Synergy::SharedTargets(partner) => Some(*partner),
+            _ => None,
+        })
+    }
+
+    /// Damage of a hit on `unit` after synergy bonuses.
+    pub(crate) fn damage(&self, damage: usize, unit: &EnemyUnit) -> usize {
+        let slowed = unit.has_status(EffectType::Slow) || unit.has_status(EffectType::Freeze);
+        if slowed && self.0.contains(&Synergy::Shatter) {
+            (damage as f32 * SHATTER_MULTIPLIER) as usize
+        } else {
+            damage
+        }
+    }
+}
+
+/// Recompute synergies from the towers around each tower whenever one is built or removed.
+pub(crate) fn synergy_system(
+    grid: Res<GridResource>,
+    added: Query<(), Added<TowerComponents>>,
+    mut removed: RemovedComponents<TowerComponents>,
+    towers: Query<&TowerComponents>,
+    mut synergies: Query<(
+        Entity,
+        &TowerComponents,
+        &TowerPosition,
+        &mut TowerSynergies,
+    )>,
+) {
+    let removed_any = removed.read().count() > 0;
+    if added.is_empty() && !removed_any {
+        return;
+    }
+
+    for (entity, tower, position, mut tower_synergies) in synergies.iter_mut() {
+        let Some(tiles) = grid.footprint(position, tower.tower.footprint()) else {
+            continue;
+        };
+        let mut next = Vec::new();
+        for tile in tiles.iter() {
+            for neighbour_tile in grid.neighbours(tile) {
+                let Some(neighbour) = grid.get_tower(neighbour_tile) else {
+                    continue;
+                };
+                if neighbour == entity {
+                    continue;
+                }
+                let Ok(neighbour_tower) = towers.get(neighbour) else {
+                    continue;
+                };
+                if let Some(synergy) =
+                    Synergy::between(tower.tower, neighbour_tower.tower, neighbour)
+                {
+                    if !next.contains(&synergy) {
+                        next.push(synergy);
+                    }
+                }
+            }
+        }
+        if tower_synergies.0 != next {
+            tower_synergies.0 = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mob::{Enemies, SpawnId, StatusEffect};
+
+    use super::*;
+
+    #[test]
+    fn test_shatter_and_shared_targets() {
+        let ice = Entity::from_raw(1);
+        let sniper = Entity::from_raw(2);
+        let shatter = Synergy::between(TowerTypes::Fire, TowerTypes::Ice, ice);
+        assert_eq!(shatter, Some(Synergy::Shatter));
+        assert_eq!(
+            Synergy::between(TowerTypes::Ice, TowerTypes::Fire, ice),
+            None
+        );
+        assert_eq!(
+            Synergy::between(TowerTypes::Sniper, TowerTypes::Sniper, sniper),
+            Some(Synergy::SharedTargets(sniper))
+        );
+
+        let fire = TowerSynergies(vec![Synergy::Shatter]);
+        let mut unit = Enemies::Block.into_unit(SpawnId { id: 0 }, 1);
+        assert_eq!(fire.damage(10, &unit), 10);
+        unit.insert_status(StatusEffect::new(EffectType::Slow, 2.0, 10, None));
+        assert_eq!(fire.damage(10, &unit), 15);
+        assert_eq!(TowerSynergies::default().damage(10, &unit), 10);
+    }
+}
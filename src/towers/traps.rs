@@ -0,0 +1,319 @@
+//! Traps placed on walkable tiles
+
+use std::fmt::Display;
+
+use bevy_ecs_tilemap::tiles::TilePos;
+use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::grid::{GridClickEvent, GridResource},
+    mob::{
+        enemy::ScheduledForDespawnEnemy, EffectType, EnemyDespawnReason, EnemyUnit,
+        MobDespawnEvent, StatusEffect,
+    },
+    player::{Player, PlayerUpdateEvent},
+    prelude::*,
+    state::{StateDespawnMarker, IN_GAME},
+};
+
+use super::SellRefund;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Sequence, Serialize, Deserialize)]
+pub(crate) enum TrapTypes {
+    Spikes,
+    Tar,
+    Mine,
+}
+
+impl Display for TrapTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl TrapTypes {
+    pub(crate) fn cost(&self) -> u32 {
+        match self {
+            TrapTypes::Spikes => 5,
+            TrapTypes::Tar => 8,
+            TrapTypes::Mine => 12,
+        }
+    }
+
+    /// How many enemies the trap triggers on before it is used up.
+    pub(crate) fn charges(&self) -> u32 {
+        match self {
+            TrapTypes::Spikes => 25,
+            TrapTypes::Tar => 40,
+            TrapTypes::Mine => 1,
+        }
+    }
+
+    pub(crate) fn damage(&self) -> usize {
+        match self {
+            TrapTypes::Spikes => 10,
+            TrapTypes::Tar => 0,
+            TrapTypes::Mine => 120,
+        }
+    }
+
+    pub(crate) fn status_effect(&self) -> Option<StatusEffect> {
+        match self {
+            TrapTypes::Spikes => Some(StatusEffect::new(EffectType::Poison, 3.0, 1, None)),
+            TrapTypes::Tar => Some(StatusEffect::new(EffectType::Slow, 2.0, 40, None)),
+            TrapTypes::Mine => Some(StatusEffect::new(EffectType::Stun, 1.0, 0, None)),
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            TrapTypes::Spikes => Color::GRAY,
+            TrapTypes::Tar => Color::DARK_GRAY,
+            TrapTypes::Mine => Color::MAROON,
+        }
+    }
+}
+
+#[derive(Debug, Component, Clone, Copy)]
+pub(crate) struct Trap {
+    pub(crate) trap_type: TrapTypes,
+    pub(crate) charges: u32,
+    pub(crate) tile: TilePos,
+}
+
+impl Trap {
+    /// Bricks returned when selling, for the charges the trap has left.
+    pub(crate) fn refund(&self, sell_refund: &SellRefund) -> u32 {
+        let left = self.trap_type.cost() * self.charges / self.trap_type.charges();
+        left * sell_refund.percent.min(100) / 100
+    }
+}
+
+/// Sent by the mob movement when an enemy steps onto a trapped tile.
+#[derive(Debug, Event)]
+pub(crate) struct TrapTriggerEvent {
+    pub(crate) trap: Entity,
+    pub(crate) enemy: Entity,
+}
+
+/// Spawns a trap on `pos`, the caller puts it on the grid.
+pub(crate) fn spawn_trap(
+    commands: &mut Commands,
+    grid: &GridResource,
+    trap_type: &TrapTypes,
+    transform: &Transform,
+    pos: TilePos,
+    charges: u32,
+) -> Entity {
+    let size = grid.grid_square_size * 0.6;
+    // Drawn under the enemies walking over it
+    let mut transform = *transform;
+    transform.translation.z = -5.0;
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: trap_type.color(),
+                    custom_size: Some(Vec2::splat(size)),
+                    ..Default::default()
+                },
+                transform,
+                ..Default::default()
+            },
+            Trap {
+                trap_type: *trap_type,
+                charges,
+                tile: pos,
+            },
+            StateDespawnMarker(IN_GAME),
+        ))
+        .id()
+}
+
+pub(crate) fn trap_click_system(
+    mut commands: Commands,
+    mut grid_event: EventReader<GridClickEvent>,
+    mut player_event: EventWriter<PlayerUpdateEvent>,
+    mut grid: ResMut<GridResource>,
+    player: Query<&Player>,
+    traps: Query<&Trap>,
+    sell_refund: Res<SellRefund>,
+) {
+    for event in grid_event.read() {
+        match event {
+            GridClickEvent::BuildTrap(trap_type, transform, pos) => {
+                let bricks = trap_type.cost();
+                if bricks > player.single().bricks {
+                    trace!("Not enough bricks");
+                    continue;
+                }
+                if grid.is_occupied(pos) || grid.get_trap(*pos).is_some() {
+                    trace!("Traps go on free walkable tiles");
+                    continue;
+                }
+
+                let entity = spawn_trap(
+                    &mut commands,
+                    &grid,
+                    trap_type,
+                    transform,
+                    *pos,
+                    trap_type.charges(),
+                );
+
+                grid.set_trap(pos, entity);
+                player_event.send(PlayerUpdateEvent::Build(bricks));
+            }
+            GridClickEvent::RemoveTrap(pos) => {
+                let Some(entity) = grid.get_trap(*pos) else {
+                    continue;
+                };
+                if let Ok(trap) = traps.get(entity) {
+                    player_event.send(PlayerUpdateEvent::Refund(trap.refund(&sell_refund)));
+                }
+                grid.remove_trap(pos);
+                commands.entity(entity).despawn();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn trap_trigger_system(
+    mut commands: Commands,
+    mut trigger_events: EventReader<TrapTriggerEvent>,
+    mut traps: Query<&mut Trap>,
+    mut enemies: Query<&mut EnemyUnit>,
+    mut grid: ResMut<GridResource>,
+    mut mob_despawn_event: EventWriter<MobDespawnEvent>,
+    mut enemy_schedule: ResMut<ScheduledForDespawnEnemy>,
+) {
+    for event in trigger_events.read() {
+        if enemy_schedule.contains(&event.enemy) {
+            continue;
+        }
+        let Ok(mut trap) = traps.get_mut(event.trap) else {
+            continue;
+        };
+        // A trap used up earlier this frame
+        if trap.charges == 0 {
+            continue;
+        }
+        let Ok(mut enemy_unit) = enemies.get_mut(event.enemy) else {
+            continue;
+        };
+
+        if trap.trap_type.damage() > 0 {
+            enemy_unit.take_damage(trap.trap_type.damage());
+        }
+        if let Some(effect) = trap.trap_type.status_effect() {
+            enemy_unit.insert_status(effect);
+        }
+        if enemy_unit.health == 0 {
+            mob_despawn_event.send(MobDespawnEvent {
+                enemy_entity: event.enemy,
+                spawner_id: enemy_unit.spwawner_id,
+                reason: EnemyDespawnReason::Killed,
+            });
+            enemy_schedule.insert(event.enemy);
+        }
+
+        trap.charges -= 1;
+        if trap.charges == 0 {
+            grid.remove_trap(&trap.tile);
+            commands.entity(event.trap).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mob::{Enemies, SpawnId};
+
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<GridClickEvent>()
+            .add_event::<TrapTriggerEvent>()
+            .add_event::<MobDespawnEvent>()
+            .add_event::<PlayerUpdateEvent>()
+            .insert_resource(GridResource::new(10, 16.0, [(0.0, 0.0); 4]))
+            .init_resource::<ScheduledForDespawnEnemy>()
+            .init_resource::<SellRefund>()
+            .add_systems(Update, (trap_click_system, trap_trigger_system).chain());
+        app.world.spawn(Player {
+            hp: 100,
+            bricks: 100,
+        });
+        app
+    }
+
+    fn build(app: &mut App, trap_type: TrapTypes, pos: TilePos) -> Entity {
+        app.world.send_event(GridClickEvent::BuildTrap(
+            trap_type,
+            Transform::default(),
+            pos,
+        ));
+        app.update();
+        app.world.resource::<GridResource>().get_trap(pos).unwrap()
+    }
+
+    fn trigger(app: &mut App, trap: Entity, enemy: Entity, times: u32) {
+        for _ in 0..times {
+            app.world.send_event(TrapTriggerEvent { trap, enemy });
+        }
+        app.update();
+    }
+
+    #[test]
+    fn test_trap_used_up() {
+        let mut app = app();
+        let pos = TilePos::new(2, 2);
+        let trap = build(&mut app, TrapTypes::Spikes, pos);
+        let enemy = app
+            .world
+            .spawn(Enemies::Block.into_unit(SpawnId { id: 0 }, 10))
+            .id();
+
+        // Level 10 blocks have a point of armor
+        trigger(&mut app, trap, enemy, 5);
+        assert_eq!(app.world.get::<Trap>(trap).unwrap().charges, 20);
+        assert_eq!(app.world.get::<EnemyUnit>(enemy).unwrap().health, 255);
+        assert!(app
+            .world
+            .get::<EnemyUnit>(enemy)
+            .unwrap()
+            .has_status(EffectType::Poison));
+
+        trigger(&mut app, trap, enemy, 20);
+        assert!(app.world.get_entity(trap).is_none());
+        assert_eq!(app.world.resource::<GridResource>().get_trap(pos), None);
+        assert_eq!(app.world.get::<EnemyUnit>(enemy).unwrap().health, 75);
+    }
+
+    #[test]
+    fn test_sell_trap() {
+        let mut app = app();
+        let pos = TilePos::new(2, 2);
+        let trap = build(&mut app, TrapTypes::Tar, pos);
+        app.world.get_mut::<Trap>(trap).unwrap().charges = 20;
+
+        app.world.send_event(GridClickEvent::RemoveTrap(pos));
+        app.update();
+        assert!(app.world.get_entity(trap).is_none());
+        assert_eq!(app.world.resource::<GridResource>().get_trap(pos), None);
+        // Half the charges are left, 70% of the half of 8 bricks comes back
+        let refunds = app
+            .world
+            .resource::<Events<PlayerUpdateEvent>>()
+            .iter_current_update_events()
+            .filter_map(|event| match event {
+                PlayerUpdateEvent::Refund(bricks) => Some(*bricks),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(refunds, vec![2]);
+    }
+}
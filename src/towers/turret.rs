@@ -0,0 +1,89 @@
+//! Turret aiming
+
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::prelude::*;
+
+/// How far off target, in radians, a turret may be and still fire.
+const AIM_TOLERANCE: f32 = 0.1;
+
+/// Turns a tower towards its target before it may fire.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub(crate) struct Turret {
+    /// Current aim as an angle from the x axis.
+    facing: f32,
+    /// Direction the fire arc is centred on.
+    rest: f32,
+    /// Radians per second.
+    turn_rate: f32,
+    /// Half the width of the fire arc, `None` turns all the way around.
+    arc: Option<f32>,
+}
+
+/// Signed shortest turn from one angle to another.
+fn angle_between(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+impl Turret {
+    pub(crate) fn new(rest: f32, turn_rate: f32, arc: Option<f32>) -> Self {
+        Turret {
+            facing: rest,
+            rest,
+            turn_rate,
+            arc,
+        }
+    }
+
+    pub(crate) fn in_arc(&self, direction: Vec2) -> bool {
+        self.arc.map_or(true, |arc| {
+            angle_between(self.rest, direction.y.atan2(direction.x)).abs() <= arc
+        })
+    }
+
+    /// Turn as far towards `direction` as the turn rate allows, returns whether it is on target.
+    pub(crate) fn turn_towards(&mut self, direction: Vec2, delta: f32) -> bool {
+        let diff = angle_between(self.facing, direction.y.atan2(direction.x));
+        let step = self.turn_rate * delta;
+        self.facing += diff.clamp(-step, step);
+        diff.abs() - step.min(diff.abs()) <= AIM_TOLERANCE
+    }
+
+    /// Sprite rotation for the current aim, the sprites are drawn facing up.
+    pub(crate) fn rotation(&self) -> Quat {
+        Quat::from_rotation_z(self.facing - FRAC_PI_2)
+    }
+
+    /// Directions of the two edges of the fire arc, if it is limited.
+    pub(crate) fn arc_edges(&self) -> Option<(Vec2, Vec2)> {
+        self.arc.map(|arc| {
+            (
+                Vec2::from_angle(self.rest - arc),
+                Vec2::from_angle(self.rest + arc),
+            )
+        })
+    }
+}
+
+pub(crate) fn turret_rotation_system(mut query: Query<(&Turret, &mut Transform), Changed<Turret>>) {
+    for (turret, mut transform) in query.iter_mut() {
+        transform.rotation = turret.rotation();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_turret_turns_before_firing() {
+        let mut turret = Turret::new(0.0, PI, Some(FRAC_PI_2));
+        assert!(turret.in_arc(Vec2::Y));
+        assert!(!turret.in_arc(Vec2::NEG_X));
+
+        // A quarter turn at half a turn per second takes half a second
+        assert!(!turret.turn_towards(Vec2::Y, 0.25));
+        assert!(turret.turn_towards(Vec2::Y, 0.25));
+        assert!((turret.facing - FRAC_PI_2).abs() < 1e-4);
+    }
+}
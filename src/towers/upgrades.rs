@@ -0,0 +1,354 @@
+//! Tower upgrade trees
+
+use serde::{Deserialize, Serialize};
+
+use crate::{mob::EffectType, prelude::*};
+
+use super::TowerTypes;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum UpgradeEffect {
+    Damage(usize),
+    /// Multiplies the reload time.
+    ReloadScale(f32),
+    Pierce(usize),
+    Count(usize),
+    /// Adds splash radius in grid squares.
+    SplashRadius(f32),
+    CritChance(f32),
+    /// Adds range in grid squares, up to the weapon's cap.
+    Range(f32),
+    /// Replaces the status effect applied on hit.
+    OnHit(EffectType),
+}
+
+#[derive(Debug)]
+pub(crate) struct UpgradeNode {
+    pub(crate) name: &'static str,
+    pub(crate) description: &'static str,
+    pub(crate) cost: u32,
+    pub(crate) effects: &'static [UpgradeEffect],
+}
+
+/// A chain of upgrades bought in order.
+#[derive(Debug)]
+pub(crate) struct UpgradeBranch {
+    pub(crate) name: &'static str,
+    pub(crate) nodes: &'static [UpgradeNode],
+}
+
+/// Exclusive branches, a tower commits to one with its first purchase.
+#[derive(Debug)]
+pub(crate) struct UpgradeTree {
+    pub(crate) branches: &'static [UpgradeBranch],
+}
+
+static BASIC_TREE: UpgradeTree = UpgradeTree {
+    branches: &[
+        UpgradeBranch {
+            name: "Overcharge",
+            nodes: &[
+                UpgradeNode {
+                    name: "Focused Lens",
+                    description: "+5 damage, +0.5 range",
+                    cost: 15,
+                    effects: &[UpgradeEffect::Damage(5), UpgradeEffect::Range(0.5)],
+                },
+                UpgradeNode {
+                    name: "Overcharged Capacitor",
+                    description: "+10% crit chance, +10 damage, hits leave enemies vulnerable",
+                    cost: 40,
+                    effects: &[
+                        UpgradeEffect::CritChance(0.1),
+                        UpgradeEffect::Damage(10),
+                        UpgradeEffect::OnHit(EffectType::Vulnerability),
+                    ],
+                },
+            ],
+        },
+        UpgradeBranch {
+            name: "Scatter",
+            nodes: &[
+                UpgradeNode {
+                    name: "Split Beam",
+                    description: "+1 projectile",
+                    cost: 20,
+                    effects: &[UpgradeEffect::Count(1)],
+                },
+                UpgradeNode {
+                    name: "Prism Array",
+                    description: "+2 projectiles",
+                    cost: 50,
+                    effects: &[UpgradeEffect::Count(2)],
+                },
+            ],
+        },
+    ],
+};
+
+static FIRE_TREE: UpgradeTree = UpgradeTree {
+    branches: &[
+        UpgradeBranch {
+            name: "Inferno",
+            nodes: &[
+                UpgradeNode {
+                    name: "Hotter Flames",
+                    description: "+15 damage",
+                    cost: 25,
+                    effects: &[UpgradeEffect::Damage(15)],
+                },
+                UpgradeNode {
+                    name: "White Heat",
+                    description: "+40 damage",
+                    cost: 70,
+                    effects: &[UpgradeEffect::Damage(40)],
+                },
+            ],
+        },
+        UpgradeBranch {
+            name: "Napalm",
+            nodes: &[
+                UpgradeNode {
+                    name: "Sticky Fuel",
+                    description: "+1 splash radius",
+                    cost: 30,
+                    effects: &[UpgradeEffect::SplashRadius(1.0)],
+                },
+                UpgradeNode {
+                    name: "Firestorm",
+                    description: "+1.5 splash radius, 20% faster reload",
+                    cost: 80,
+                    effects: &[
+                        UpgradeEffect::SplashRadius(1.5),
+                        UpgradeEffect::ReloadScale(0.8),
+                    ],
+                },
+            ],
+        },
+    ],
+};
+
+static ICE_TREE: UpgradeTree = UpgradeTree {
+    branches: &[
+        UpgradeBranch {
+            name: "Freeze",
+            nodes: &[
+                UpgradeNode {
+                    name: "Flash Freeze",
+                    description: "Hits freeze enemies in place",
+                    cost: 30,
+                    effects: &[UpgradeEffect::OnHit(EffectType::Freeze)],
+                },
+                UpgradeNode {
+                    name: "Cryo Shards",
+                    description: "+1 projectile, +5 damage",
+                    cost: 70,
+                    effects: &[UpgradeEffect::Count(1), UpgradeEffect::Damage(5)],
+                },
+            ],
+        },
+        UpgradeBranch {
+            name: "Blizzard",
+            nodes: &[
+                UpgradeNode {
+                    name: "Frost Nova",
+                    description: "Slow spreads over a 1.5 square radius",
+                    cost: 30,
+                    effects: &[UpgradeEffect::SplashRadius(1.5)],
+                },
+                UpgradeNode {
+                    name: "Whiteout",
+                    description: "+1 splash radius, 20% faster reload",
+                    cost: 70,
+                    effects: &[
+                        UpgradeEffect::SplashRadius(1.0),
+                        UpgradeEffect::ReloadScale(0.8),
+                    ],
+                },
+            ],
+        },
+    ],
+};
+
+static SNIPER_TREE: UpgradeTree = UpgradeTree {
+    branches: &[
+        UpgradeBranch {
+            name: "Armor Piercing",
+            nodes: &[
+                UpgradeNode {
+                    name: "Shredder Rounds",
+                    description: "+15 damage, hits shred enemy armor",
+                    cost: 40,
+                    effects: &[
+                        UpgradeEffect::Damage(15),
+                        UpgradeEffect::OnHit(EffectType::ArmorShred),
+                    ],
+                },
+                UpgradeNode {
+                    name: "Tungsten Core",
+                    description: "+2 pierce, +30 damage",
+                    cost: 100,
+                    effects: &[UpgradeEffect::Pierce(2), UpgradeEffect::Damage(30)],
+                },
+            ],
+        },
+        UpgradeBranch {
+            name: "Rapid Fire",
+            nodes: &[
+                UpgradeNode {
+                    name: "Light Trigger",
+                    description: "30% faster reload",
+                    cost: 40,
+                    effects: &[UpgradeEffect::ReloadScale(0.7)],
+                },
+                UpgradeNode {
+                    name: "Autoloader",
+                    description: "40% faster reload",
+                    cost: 100,
+                    effects: &[UpgradeEffect::ReloadScale(0.6)],
+                },
+            ],
+        },
+    ],
+};
+
+/// Towers without a weapon have nothing to upgrade.
+static EMPTY_TREE: UpgradeTree = UpgradeTree { branches: &[] };
+
+impl TowerTypes {
+    pub(crate) fn upgrade_tree(&self) -> &'static UpgradeTree {
+        match self {
+            TowerTypes::Basic => &BASIC_TREE,
+            TowerTypes::Fire => &FIRE_TREE,
+            TowerTypes::Ice => &ICE_TREE,
+            TowerTypes::Sniper => &SNIPER_TREE,
+            TowerTypes::Amplifier
+            | TowerTypes::Scope
+            | TowerTypes::Overclock
+            | TowerTypes::Radar
+            | TowerTypes::Wall => &EMPTY_TREE,
+        }
+    }
+}
+
+/// The branch a tower has committed to and how far along it is.
+#[derive(Debug, Component, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TowerUpgrades {
+    branch: Option<usize>,
+    tier: usize,
+}
+
+impl TowerUpgrades {
+    pub(crate) fn purchased(&self, branch: usize, tier: usize) -> bool {
+        self.branch == Some(branch) && tier < self.tier
+    }
+
+    pub(crate) fn available(&self, branch: usize, tier: usize) -> bool {
+        self.branch.map_or(true, |chosen| chosen == branch) && tier == self.tier
+    }
+
+    pub(crate) fn next(
+        &self,
+        tree: &'static UpgradeTree,
+        branch: usize,
+    ) -> Option<&'static UpgradeNode> {
+        if !self.available(branch, self.tier) {
+            return None;
+        }
+        tree.branches.get(branch)?.nodes.get(self.tier)
+    }
+
+    pub(crate) fn purchase(&mut self, branch: usize) {
+        self.branch = Some(branch);
+        self.tier += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy_ecs_tilemap::tiles::TilePos;
+
+    use crate::{
+        arena::grid::{GridClickEvent, GridResource},
+        player::Player,
+        rng::GameRng,
+        towers::{tower_upgrade_system, TowerComponents, TowerInfo, TowerSpending},
+        weapons::weapon::{WeaponComponent, WeaponTypes},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_next_node_and_exclusive_branches() {
+        let tree = TowerTypes::Sniper.upgrade_tree();
+        let mut upgrades = TowerUpgrades::default();
+        assert_eq!(upgrades.next(tree, 0).unwrap().name, "Shredder Rounds");
+        assert_eq!(upgrades.next(tree, 1).unwrap().name, "Light Trigger");
+        assert!(upgrades.next(tree, 2).is_none());
+
+        upgrades.purchase(0);
+        assert!(upgrades.purchased(0, 0));
+        assert_eq!(upgrades.next(tree, 0).unwrap().name, "Tungsten Core");
+        // The first purchase locks the other branch
+        assert!(!upgrades.available(1, 0));
+        assert!(upgrades.next(tree, 1).is_none());
+
+        upgrades.purchase(0);
+        assert!(upgrades.next(tree, 0).is_none());
+    }
+
+    #[test]
+    fn test_purchase_costs_bricks() {
+        let mut app = App::new();
+        app.add_event::<GridClickEvent>()
+            .init_resource::<TowerInfo>()
+            .init_resource::<GameRng>()
+            .add_systems(Update, tower_upgrade_system);
+        app.world.spawn(Player {
+            hp: 100,
+            bricks: 50,
+        });
+        let tower = app
+            .world
+            .spawn((
+                TowerComponents {
+                    tower: TowerTypes::Sniper,
+                },
+                WeaponComponent::from(WeaponTypes::Rifle),
+                TowerUpgrades::default(),
+                TowerSpending::new(15, 1),
+            ))
+            .id();
+        let pos = TilePos::new(2, 2);
+        let mut grid = GridResource::new(10, 16.0, [(0.0, 0.0); 4]);
+        grid.set_occupied(&pos, tower);
+        app.insert_resource(grid);
+        let damage = app
+            .world
+            .get::<WeaponComponent>(tower)
+            .unwrap()
+            .projectile_data
+            .damage;
+
+        let bricks = |app: &mut App| app.world.query::<&Player>().single(&app.world).bricks;
+        app.world.send_event(GridClickEvent::UpgradeBranch(pos, 0));
+        app.update();
+        assert_eq!(bricks(&mut app), 10);
+        assert_eq!(app.world.get::<TowerUpgrades>(tower).unwrap().tier, 1);
+        assert_eq!(app.world.get::<TowerSpending>(tower).unwrap().spent, 55);
+        let data = app
+            .world
+            .get::<WeaponComponent>(tower)
+            .unwrap()
+            .projectile_data;
+        assert_eq!(data.damage, damage + 15);
+        assert_eq!(data.effect_type, EffectType::ArmorShred);
+
+        // Too few bricks for the next node, and the other branch is locked
+        app.world.send_event(GridClickEvent::UpgradeBranch(pos, 0));
+        app.world.send_event(GridClickEvent::UpgradeBranch(pos, 1));
+        app.update();
+        assert_eq!(bricks(&mut app), 10);
+        assert_eq!(app.world.get::<TowerUpgrades>(tower).unwrap().tier, 1);
+    }
+}
@@ -15,8 +15,9 @@ use leafwing_input_manager::{
 use pathfinding::grid;
 
 use crate::{
-    arena::grid::{GridClickEvent, HighlightedSpot},
-    towers::{self, TowerComponents, TowerPosition, TowerTypes},
+    arena::grid::{GridClickEvent, GridResource, HighlightedSpot},
+    state::GameplaySet,
+    towers::{self, ghost::GhostTower, TowerComponents, TowerTypes},
 };
 
 /// Plugin to handle user input
@@ -25,8 +26,8 @@ pub struct InputPlugin;
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_input_manager)
-            .add_systems(Update, keyboard_input_system)
-            .add_systems(Update, keyboard_action_system)
+            .add_systems(Update, keyboard_input_system.in_set(GameplaySet))
+            .add_systems(Update, keyboard_action_system.in_set(GameplaySet))
             .add_plugins(InputManagerPlugin::<TowerTypes>::default())
             .add_plugins(InputManagerPlugin::<ActionKeys>::default())
             .insert_resource(ClashStrategy::PrioritizeLongest);
@@ -53,6 +54,11 @@ fn spawn_input_manager(mut commands: Commands) {
     input_map.insert(Fire, KeyF);
     input_map.insert(Ice, KeyI);
     input_map.insert(Sniper, KeyS);
+    input_map.insert(Amplifier, KeyA);
+    input_map.insert(Scope, KeyO);
+    input_map.insert(Overclock, KeyC);
+    input_map.insert(Radar, KeyR);
+    input_map.insert(Wall, KeyW);
     action_map.insert(ActionKeys::RemoveTower, Backspace);
     action_map.insert(ActionKeys::UpgradeTower, KeyU);
 
@@ -64,9 +70,11 @@ fn keyboard_input_system(
     keyboard_input: Query<&ActionState<TowerTypes>>,
     highlightedspot: Res<HighlightedSpot>,
     mut grid_click_events: EventWriter<GridClickEvent>,
+    mut ghost_tower: ResMut<GhostTower>,
 ) {
     let action = keyboard_input.single();
     for act in action.get_just_pressed() {
+        ghost_tower.0 = Some(act);
         if let Some((_, transform, pos)) = highlightedspot.0 {
             grid_click_events.send(GridClickEvent::BuildTower(act.clone(), transform, pos));
         }
@@ -77,29 +85,30 @@ fn keyboard_action_system(
     keyboard_input: Query<&ActionState<ActionKeys>>,
     highlightedspot: Res<HighlightedSpot>,
     mut grid_click_events: EventWriter<GridClickEvent>,
-    tower_query: Query<(&TowerComponents, &TowerPosition)>,
+    tower_query: Query<&TowerComponents>,
+    grid: Res<GridResource>,
 ) {
     let action = keyboard_input.single();
     for act in action.get_just_pressed() {
-        match act {
-            ActionKeys::RemoveTower => {
-                if let Some((_, _, pos)) = highlightedspot.0 {
-                    for (tower, tower_pos) in tower_query.iter() {
-                        if tower_pos.0 == pos {
-                            grid_click_events.send(GridClickEvent::RemoveTower(tower.tower, pos));
-                        }
-                    }
-                }
+        // Any tile of a large tower selects it
+        let Some((_, _, pos)) = highlightedspot.0 else {
+            continue;
+        };
+        let tower = grid
+            .get_tower(pos)
+            .and_then(|entity| tower_query.get(entity).ok());
+        match (act, tower) {
+            (ActionKeys::RemoveTower, Some(tower)) => {
+                grid_click_events.send(GridClickEvent::RemoveTower(tower.tower, pos));
             }
-            ActionKeys::UpgradeTower => {
-                if let Some((_, _, pos)) = highlightedspot.0 {
-                    for (tower, tower_pos) in tower_query.iter() {
-                        if tower_pos.0 == pos {
-                            grid_click_events.send(GridClickEvent::UpgradeTower(tower.tower, pos));
-                        }
-                    }
-                }
+            // The same key sells a trap when no tower stands on the tile
+            (ActionKeys::RemoveTower, None) if grid.get_trap(pos).is_some() => {
+                grid_click_events.send(GridClickEvent::RemoveTrap(pos));
             }
+            (ActionKeys::UpgradeTower, Some(tower)) => {
+                grid_click_events.send(GridClickEvent::UpgradeTower(tower.tower, pos));
+            }
+            _ => {}
         }
     }
 }
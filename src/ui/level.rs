@@ -1,13 +1,15 @@
 //! Map level
 
 use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     mob::{enemy, EnemyComponent},
     prelude::*,
+    state::{AppState, GameplaySet, RenderSet},
 };
 
-#[derive(Debug, Clone, Resource)]
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
 pub(crate) struct MapLevel {
     pub(crate) level: u32,
 }
@@ -31,12 +33,20 @@ impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(MapLevel::default())
             .add_event::<LevelMap>()
-            .add_systems(Update, level_up_map_system)
-            .add_systems(Update, ui_system);
+            .add_systems(FixedUpdate, level_up_map_system.in_set(GameplaySet))
+            .add_systems(OnEnter(AppState::GameCreate), reset_level)
+            .add_systems(Update, ui_system.in_set(RenderSet));
     }
 }
 
-fn level_up_map_system(mut level: ResMut<MapLevel>, mut level_up_map: EventReader<LevelMap>) {
+fn reset_level(mut commands: Commands) {
+    commands.insert_resource(MapLevel::default());
+}
+
+pub(crate) fn level_up_map_system(
+    mut level: ResMut<MapLevel>,
+    mut level_up_map: EventReader<LevelMap>,
+) {
     for event in level_up_map.read() {
         match event {
             LevelMap::LevelUp(amount) => {
@@ -0,0 +1,261 @@
+//! Start, pause and game over screens and the speed bar
+
+use bevy::app::AppExit;
+use bevy_egui::{egui, EguiContexts};
+use enum_iterator::all;
+
+use crate::{
+    prelude::*,
+    replay::ReplayEvent,
+    save::{SaveEvent, SaveMessage},
+    scores::{HighScores, ScoreColumn},
+    session::{Difficulty, GameSettings, GameSummary, MapLayout},
+    state::{AppState, GameSpeed},
+    towers::{TowerInfo, TowerTypes},
+    ui::level::MapLevel,
+};
+
+/// The menu plugin.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (start_menu_system, high_scores_system).run_if(in_state(AppState::StartMenu)),
+        )
+        .add_systems(
+            Update,
+            pause_menu_system.run_if(in_state(AppState::GamePaused)),
+        )
+        .add_systems(
+            Update,
+            game_over_system.run_if(in_state(AppState::GameOver)),
+        )
+        .add_systems(
+            Update,
+            speed_bar_system
+                .run_if(in_state(AppState::GameRunning).or_else(in_state(AppState::GamePaused))),
+        );
+    }
+}
+
+fn menu_window(title: &str) -> egui::Window<'_> {
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+}
+
+fn start_menu_system(
+    mut context: EguiContexts,
+    mut settings: ResMut<GameSettings>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut replay_events: EventWriter<ReplayEvent>,
+    save_message: Res<SaveMessage>,
+    mut exit: EventWriter<AppExit>,
+) {
+    menu_window("Rusty Apple").show(context.ctx_mut(), |ui| {
+        ui.label("Map");
+        ui.horizontal(|ui| {
+            for map in all::<MapLayout>() {
+                ui.selectable_value(&mut settings.map, map, map.to_string());
+            }
+        });
+        ui.label("Difficulty");
+        ui.horizontal(|ui| {
+            for difficulty in all::<Difficulty>() {
+                ui.selectable_value(&mut settings.difficulty, difficulty, difficulty.to_string())
+                    .on_hover_text(format!(
+                        "{} HP, {} Bricks",
+                        difficulty.player_hp(),
+                        difficulty.starting_bricks()
+                    ));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            ui.add(egui::DragValue::new(&mut settings.seed));
+        });
+        ui.separator();
+        if ui.button("Start (Enter)").clicked() {
+            next_state.set(AppState::GameCreate);
+        }
+        if ui.button("Watch Replay").clicked() {
+            replay_events.send(ReplayEvent::Watch);
+        }
+        if ui.button("Quit").clicked() {
+            exit.send(AppExit);
+        }
+        if let Some(message) = &save_message.0 {
+            ui.label(message);
+        }
+    });
+}
+
+/// The column the high scores are sorted by.
+struct ScoreSort {
+    column: ScoreColumn,
+    descending: bool,
+}
+
+impl Default for ScoreSort {
+    fn default() -> Self {
+        ScoreSort {
+            column: ScoreColumn::default(),
+            descending: true,
+        }
+    }
+}
+
+fn high_scores_system(
+    mut context: EguiContexts,
+    scores: Res<HighScores>,
+    mut sort: Local<ScoreSort>,
+) {
+    egui::Window::new("High Scores")
+        .default_open(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .show(context.ctx_mut(), |ui| {
+            if scores.edited {
+                ui.label("The score file was edited by hand, its scores were ignored");
+            }
+            if scores.entries.is_empty() {
+                ui.label("No finished runs yet");
+                return;
+            }
+            egui::Grid::new("high_scores").striped(true).show(ui, |ui| {
+                // Click a heading to sort by it, again to flip the order
+                for column in all::<ScoreColumn>() {
+                    let selected = column == sort.column;
+                    let label = match (selected, sort.descending) {
+                        (true, true) => format!("{} ⬇", column),
+                        (true, false) => format!("{} ⬆", column),
+                        (false, _) => column.to_string(),
+                    };
+                    if ui.selectable_label(selected, label).clicked() {
+                        if selected {
+                            sort.descending = !sort.descending;
+                        } else {
+                            *sort = ScoreSort {
+                                column,
+                                descending: true,
+                            };
+                        }
+                    }
+                }
+                ui.end_row();
+                for entry in scores.sorted(sort.column, sort.descending) {
+                    for column in all::<ScoreColumn>() {
+                        ui.label(column.value(&entry));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn pause_menu_system(
+    mut context: EguiContexts,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut save_events: EventWriter<SaveEvent>,
+    mut replay_events: EventWriter<ReplayEvent>,
+    save_message: Res<SaveMessage>,
+) {
+    menu_window("Paused").show(context.ctx_mut(), |ui| {
+        if ui.button("Resume (P)").clicked() {
+            next_state.set(AppState::GameRunning);
+        }
+        if ui.button("Save").clicked() {
+            save_events.send(SaveEvent::Save);
+        }
+        if ui.button("Load").clicked() {
+            save_events.send(SaveEvent::Load);
+        }
+        if ui.button("Save Replay").clicked() {
+            replay_events.send(ReplayEvent::Save);
+        }
+        if let Some(message) = &save_message.0 {
+            ui.label(message);
+        }
+        if ui.button("Restart").clicked() {
+            next_state.set(AppState::GameCreate);
+        }
+        if ui.button("Quit to Menu").clicked() {
+            next_state.set(AppState::StartMenu);
+        }
+    });
+}
+
+fn game_over_system(
+    mut context: EguiContexts,
+    mut next_state: ResMut<NextState<AppState>>,
+    map_level: Res<MapLevel>,
+    summary: Res<GameSummary>,
+    tower_info: Res<TowerInfo>,
+    mut replay_events: EventWriter<ReplayEvent>,
+    save_message: Res<SaveMessage>,
+) {
+    menu_window("Game Over").show(context.ctx_mut(), |ui| {
+        ui.label(format!("Final Level: {}", map_level.level));
+        ui.label(format!("Kills: {}", summary.kills));
+        ui.label(format!("Leaks: {}", summary.leaks));
+        let seconds = summary.time_survived as u32;
+        ui.label(format!(
+            "Time Survived: {}:{:02}",
+            seconds / 60,
+            seconds % 60
+        ));
+        ui.separator();
+        // Totals of what hits and effects took off enemies, after synergies and armor
+        ui.label("Damage Dealt by Tower Type");
+        for tower in all::<TowerTypes>().filter(|tower| tower.weapon().is_some()) {
+            ui.label(format!("{}: {}", tower, tower_info.get_damage(&tower)));
+        }
+        ui.separator();
+        if ui.button("Play Again (Enter)").clicked() {
+            next_state.set(AppState::GameCreate);
+        }
+        if ui.button("Save Replay").clicked() {
+            replay_events.send(ReplayEvent::Save);
+        }
+        if ui.button("Quit to Menu").clicked() {
+            next_state.set(AppState::StartMenu);
+        }
+        if let Some(message) = &save_message.0 {
+            ui.label(message);
+        }
+    });
+}
+
+fn speed_bar_system(
+    mut context: EguiContexts,
+    mut speed: ResMut<GameSpeed>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    egui::Window::new("Speed")
+        .collapsible(false)
+        .resizable(false)
+        .title_bar(false)
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 4.0])
+        .show(context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                let paused = *state.get() == AppState::GamePaused;
+                if ui.selectable_label(paused, "Pause (P)").clicked() {
+                    next_state.set(if paused {
+                        AppState::GameRunning
+                    } else {
+                        AppState::GamePaused
+                    });
+                }
+                for (key, game_speed) in all::<GameSpeed>().enumerate() {
+                    let label = format!("{} ({})", game_speed, key + 1);
+                    if ui.selectable_label(*speed == game_speed, label).clicked() {
+                        *speed = game_speed;
+                    }
+                }
+            });
+        });
+}
@@ -14,21 +14,24 @@ use enum_iterator::all;
 
 use crate::{
     arena::{
-        grid::{self, GridClickEvent, GridResource, HighlightedSpot},
+        grid::{self, GridClickEvent, GridResource, HighlightedSpot, HoveredSpot},
         path_finding::Pos,
     },
     player,
-    towers::{TowerData, TowerInfo, TowerTypes},
+    state::GameplaySet,
+    towers::{check_placement, ghost::GhostTower, traps::TrapTypes, TowerInfo, TowerTypes},
     weapons::weapon::{WeaponComponent, WeaponTypes},
 };
 use bevy_ecs_tilemap::prelude::*;
 
 /// Input Plugin
 pub use input::InputPlugin;
+pub use menu::MenuPlugin;
 pub use stats::StatsPlugin;
 
 pub(crate) mod input;
 pub(crate) mod level;
+pub(crate) mod menu;
 pub(crate) mod stats;
 
 /// Ui Plugin
@@ -36,9 +39,14 @@ pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, mouse_clicked)
-            .add_systems(Update, ui_system)
-            .add_systems(Update, track_mouse_position_system)
+        app.add_systems(Update, mouse_clicked.in_set(GameplaySet))
+            .add_systems(Update, ui_system.in_set(GameplaySet))
+            .add_systems(
+                Update,
+                (track_mouse_position_system, hover_system)
+                    .chain()
+                    .in_set(GameplaySet),
+            )
             .insert_resource(MousePosition::default())
             .add_plugins(level::LevelPlugin);
     }
@@ -57,6 +65,31 @@ fn track_mouse_position_system(
     }
 }
 
+fn hover_system(
+    mut context: EguiContexts,
+    mouse_position: Res<MousePosition>,
+    grid: Res<GridResource>,
+    window: Query<&Window>,
+    mut hovered_spot: ResMut<HoveredSpot>,
+) {
+    // Windows hide the grid below them
+    let hovered = if context.ctx_mut().is_pointer_over_area() {
+        None
+    } else {
+        let screen_size = get_screen_size(window);
+        let translated_mouse_position =
+            translate_mouse_coords(mouse_position.position, screen_size);
+        map_to_grid(translated_mouse_position, &grid).map(|(x, y)| {
+            let grid_coords = grid.grid_transform[x][y];
+            (
+                Transform::from_xyz(grid_coords.x, grid_coords.y, 0.0),
+                TilePos::new(x as u32, y as u32),
+            )
+        })
+    };
+    hovered_spot.set_if_neq(HoveredSpot(hovered));
+}
+
 fn get_screen_size(windows: Query<&Window>) -> Vec2 {
     let window = windows.single();
     Vec2::new(window.width(), window.height())
@@ -109,9 +142,14 @@ fn mouse_clicked(
     grid: Res<GridResource>,
     window: Query<&Window>,
     mut grid_click_event: EventWriter<GridClickEvent>,
+    mut ghost_tower: ResMut<GhostTower>,
 ) {
     let screen_size = get_screen_size(window);
     for event in mouse_button_input.get_just_pressed() {
+        // Right click puts the selected tower away
+        if event == &mouse::MouseButton::Right {
+            ghost_tower.0 = None;
+        }
         if event == &mouse::MouseButton::Left {
             if context.ctx_mut().wants_pointer_input() {
                 continue;
@@ -138,6 +176,8 @@ fn ui_system(
     highlighted_spot: ResMut<HighlightedSpot>,
     tower_info: Res<TowerInfo>,
     player: Query<&player::Player>,
+    grid: Res<GridResource>,
+    mut ghost_tower: ResMut<GhostTower>,
 ) {
     egui::Window::new("Weapons")
         .collapsible(false)
@@ -150,25 +190,75 @@ fn ui_system(
                 let tower_cost = tower_info.get_cost(&tower);
                 let player_bricks = player.single().bricks;
                 let button_text = format!(
-                    "Type: {:?} ( Level {} )",
+                    "Type: {:?} ( Research {} )",
                     tower,
-                    tower_info.get_level(&tower)
+                    tower_info.get_research(&tower)
                 );
                 let enabled = player_bricks >= tower_cost;
-                let button = egui::Button::new(button_text);
+                let button = egui::Button::new(button_text).selected(ghost_tower.0 == Some(tower));
                 let button = if enabled {
                     button.stroke(egui::Stroke::new(1.0, egui::Color32::WHITE))
                 } else {
                     button.stroke(egui::Stroke::new(1.0, egui::Color32::DARK_RED))
                 };
 
-                if ui.add_enabled(enabled, button).clicked() {
-                    if let Some((entity, transform, pos)) = highlighted_spot.0 {
-                        grid_click_event.send(GridClickEvent::BuildTower(tower, transform, pos));
+                let research_cost = tower_info.research_cost(&tower);
+                let research_button = egui::Button::new(format!("Research ({})", research_cost));
+
+                let hover_text = match highlighted_spot.0 {
+                    Some((_, _, pos)) => match check_placement(&grid, &tower, &pos) {
+                        Ok(_) => format!("{} Bricks", tower_cost),
+                        Err(error) => format!("{} Bricks\n{}", tower_cost, error),
+                    },
+                    None => format!("{} Bricks", tower_cost),
+                };
+
+                ui.horizontal(|ui| {
+                    let response = ui
+                        .add_enabled(enabled, button)
+                        .on_hover_text(&hover_text)
+                        .on_disabled_hover_text(&hover_text);
+                    if response.clicked() {
+                        // Stays selected, so it is previewed on the hovered tile
+                        ghost_tower.0 = Some(tower);
+                        if let Some((entity, transform, pos)) = highlighted_spot.0 {
+                            grid_click_event
+                                .send(GridClickEvent::BuildTower(tower, transform, pos));
+                            grid_click_event.send(GridClickEvent::DeHighlight(transform, pos));
+                        }
+                    }
+                    // Research only improves weapons
+                    if tower.weapon().is_some()
+                        && ui
+                            .add_enabled(player_bricks >= research_cost, research_button)
+                            .clicked()
+                    {
+                        grid_click_event.send(GridClickEvent::ResearchTower(tower));
+                    }
+                });
+            }
+
+            ui.separator();
+            let player_bricks = player.single().bricks;
+            for trap in all::<TrapTypes>() {
+                let button =
+                    egui::Button::new(format!("Trap: {} ( {} Bricks )", trap, trap.cost()));
+                if ui
+                    .add_enabled(player_bricks >= trap.cost(), button)
+                    .clicked()
+                {
+                    if let Some((_, transform, pos)) = highlighted_spot.0 {
+                        grid_click_event.send(GridClickEvent::BuildTrap(trap, transform, pos));
                         grid_click_event.send(GridClickEvent::DeHighlight(transform, pos));
                     }
                 }
             }
+            if let Some((_, transform, pos)) = highlighted_spot.0 {
+                if grid.get_trap(pos).is_some() && ui.button("Sell Trap (Backspace)").clicked() {
+                    grid_click_event.send(GridClickEvent::RemoveTrap(pos));
+                    grid_click_event.send(GridClickEvent::DeHighlight(transform, pos));
+                }
+            }
         });
 }
 
@@ -4,10 +4,18 @@ use bevy::sprite::Anchor;
 use bevy_egui::{egui, EguiContexts};
 
 use crate::{
-    arena::grid::{GridResource, HighlightedSpot},
+    arena::grid::{GridClickEvent, GridResource, HighlightedSpot},
     mob::{enemy, EnemyComponent},
+    player::Player,
     prelude::*,
-    towers::{TowerComponents, TowerInfo, TowerPosition},
+    state::GameplaySet,
+    towers::{
+        aura::{Aura, TowerModifiers},
+        synergy::TowerSynergies,
+        upgrades::TowerUpgrades,
+        SellRefund, TowerComponents, TowerInfo, TowerProgress, TowerSpending,
+    },
+    ui::level::MapLevel,
     weapons::weapon::WeaponComponent,
 };
 
@@ -16,7 +24,7 @@ pub struct StatsPlugin;
 
 impl Plugin for StatsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, ui_system);
+        app.add_systems(Update, ui_system.in_set(GameplaySet));
     }
 }
 
@@ -24,30 +32,101 @@ impl Plugin for StatsPlugin {
 fn ui_system(
     mut context: EguiContexts,
     highlighted_spot: Res<HighlightedSpot>,
-    tower_query: Query<(&TowerComponents, &WeaponComponent, &TowerPosition)>,
+    tower_query: Query<(
+        Entity,
+        &TowerComponents,
+        Option<(&WeaponComponent, &TowerModifiers)>,
+        Option<&Aura>,
+        &TowerProgress,
+        &TowerUpgrades,
+        &TowerSpending,
+        &TowerSynergies,
+    )>,
     grid: Res<GridResource>,
     tower_info: Res<TowerInfo>,
+    player: Query<&Player>,
+    mut grid_click_event: EventWriter<GridClickEvent>,
+    map_level: Res<MapLevel>,
+    sell_refund: Res<SellRefund>,
 ) {
     if let Some((x, y, z)) = highlighted_spot.0 {
-        for (tower, weapon, pos) in tower_query.iter() {
-            if z == pos.0 {
-                let weapon_level = weapon.level;
-                let mut projectile_data = weapon.projectile_data.clone();
-                tower_info.mega_fire(&tower.tower, &mut projectile_data);
-                let range = weapon
-                    .weapon_type()
-                    .range(&grid, tower_info.get_level(&tower.tower));
+        for (entity, tower, weapon, aura, progress, upgrades, spending, synergies) in
+            tower_query.iter()
+        {
+            // Any tile of a large tower selects it
+            if grid.get_tower(z) == Some(entity) {
                 let tower_damage = tower_info.get_damage(&tower.tower);
+                let refund = spending.refund(map_level.level, &sell_refund);
                 egui::Window::new("Stats")
                     .collapsible(false)
                     .auto_sized()
                     .movable(false)
                     .anchor(egui::Align2::RIGHT_TOP, egui::Vec2::new(0.0, 0.0))
                     .show(context.ctx_mut(), |ui| {
-                        ui.label(format!("Weapon Level: {}", weapon_level));
-                        ui.label(format!("{}", projectile_data));
-                        ui.label(format!("Range: {}", range));
-                        ui.label(format!("DPS: {}", tower_damage));
+                        ui.label(format!(
+                            "Tower Level: {} ( {}/{} XP )",
+                            progress.get_level(),
+                            progress.get_experience(),
+                            progress.experience_needed()
+                        ));
+                        ui.label(format!("Kills: {}", progress.get_kills()));
+                        ui.label(format!(
+                            "Research: {}",
+                            tower_info.get_research(&tower.tower)
+                        ));
+                        if let Some((weapon, modifiers)) = weapon {
+                            let mut projectile_data = weapon.projectile_data.clone();
+                            tower_info.mega_fire(&tower.tower, progress, &mut projectile_data);
+                            modifiers.apply(&mut projectile_data);
+                            let range = weapon.range(&grid, progress.get_level()) * modifiers.range;
+                            ui.label(format!("Weapon Level: {}", weapon.level));
+                            ui.label(format!("{}", projectile_data));
+                            ui.label(format!("Range: {}", range));
+                            if modifiers.is_buffed() {
+                                ui.label(format!("{}", modifiers));
+                            }
+                        }
+                        if let Some(aura) = aura {
+                            ui.label(format!("Aura: {}", aura));
+                        }
+                        for synergy in synergies.0.iter() {
+                            ui.label(format!("{}", synergy));
+                        }
+                        ui.label(format!("Damage Dealt: {}", progress.get_damage()));
+                        ui.label(format!("{} Damage: {}", tower.tower, tower_damage));
+                        ui.label(format!("Bricks Spent: {}", spending.get_spent()));
+                        if ui.button(format!("Sell ( +{} Bricks )", refund)).clicked() {
+                            grid_click_event.send(GridClickEvent::RemoveTower(tower.tower, z));
+                        }
+
+                        ui.separator();
+                        ui.label("Upgrades");
+                        let player_bricks = player.single().bricks;
+                        let tree = tower.tower.upgrade_tree();
+                        for (branch, upgrade_branch) in tree.branches.iter().enumerate() {
+                            ui.label(upgrade_branch.name);
+                            for (tier, node) in upgrade_branch.nodes.iter().enumerate() {
+                                if upgrades.purchased(branch, tier) {
+                                    ui.label(format!("[x] {}", node.name))
+                                        .on_hover_text(node.description);
+                                    continue;
+                                }
+                                let enabled =
+                                    upgrades.available(branch, tier) && player_bricks >= node.cost;
+                                let button = egui::Button::new(format!(
+                                    "{} ( {} Bricks )",
+                                    node.name, node.cost
+                                ));
+                                if ui
+                                    .add_enabled(enabled, button)
+                                    .on_hover_text(node.description)
+                                    .on_disabled_hover_text(node.description)
+                                    .clicked()
+                                {
+                                    grid_click_event.send(GridClickEvent::UpgradeBranch(z, branch));
+                                }
+                            }
+                        }
                     });
             }
         }
@@ -1,10 +1,15 @@
 //! Weapons
 use std::collections::HashSet;
 
-use crate::prelude::*;
+use crate::{
+    prelude::*,
+    rng::GameRng,
+    state::{AppState, GameplaySet, RenderSet},
+};
 
 use self::weapon::{
-    despawn_project_system, despawn_timer_system, weapon_fire_system, ProjectileData, WeaponTypes,
+    despawn_project_system, despawn_timer_system, draw_splash_system, splash_system,
+    weapon_fire_system, ProjectileData, WeaponTypes,
 };
 pub(crate) mod weapon;
 
@@ -45,6 +50,14 @@ pub(crate) struct DespawnProjectileEvent {
     pub(crate) projectile_entity: Entity,
 }
 
+#[derive(Debug, Clone, Event)]
+pub(crate) struct SplashEvent {
+    /// The centre of the explosion.
+    pub(crate) position: Vec2,
+    /// The explosion radius in world units.
+    pub(crate) radius: f32,
+}
+
 #[derive(Debug, Clone, Event)]
 pub(crate) struct WeaponUpgradeEvent {
     pub(crate) weapon: WeaponTypes,
@@ -56,9 +69,18 @@ impl Plugin for WeaponPlugin {
         app.add_event::<FireWeaponEvent>()
             .add_event::<DespawnProjectileEvent>()
             .insert_resource(ScheduledForDespawnProjectile::default())
+            .init_resource::<GameRng>()
             .add_event::<WeaponUpgradeEvent>()
-            .add_systems(Update, weapon_fire_system)
-            .add_systems(Update, despawn_project_system)
-            .add_systems(Update, despawn_timer_system);
+            .add_event::<SplashEvent>()
+            .add_systems(FixedUpdate, weapon_fire_system.in_set(GameplaySet))
+            .add_systems(FixedUpdate, despawn_project_system.in_set(GameplaySet))
+            .add_systems(FixedUpdate, despawn_timer_system.in_set(GameplaySet))
+            .add_systems(FixedUpdate, splash_system.in_set(GameplaySet))
+            .add_systems(Update, draw_splash_system.in_set(RenderSet))
+            .add_systems(OnEnter(AppState::GameCreate), reset_projectiles);
     }
 }
+
+fn reset_projectiles(mut projectile_schedule: ResMut<ScheduledForDespawnProjectile>) {
+    projectile_schedule.clear();
+}
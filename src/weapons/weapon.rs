@@ -1,21 +1,24 @@
 //! This module contains the weapon system
+use std::collections::HashSet;
 use std::hash::Hash;
 use std::time::Duration;
 
 use crate::arena::grid::GridResource;
 use crate::collision::GameLayer;
 use crate::mob::{EffectType, StatusEffect};
-use crate::towers::{TowerData, TowerInfo};
+use crate::state::{StateDespawnMarker, IN_GAME};
+use crate::towers::{upgrades::UpgradeEffect, TowerStatusEffects};
 use crate::{assets::SpriteAssets, prelude::*};
 use bevy::log::tracing_subscriber::fmt::format::Format;
 use enum_iterator::Sequence;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::fmt::Formatter;
 
-use super::{DespawnProjectileEvent, FireWeaponEvent, ScheduledForDespawnProjectile};
+use super::{DespawnProjectileEvent, FireWeaponEvent, ScheduledForDespawnProjectile, SplashEvent};
 
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Sequence)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Sequence, Serialize, Deserialize)]
 pub(crate) enum WeaponTypes {
     Laser,
     Fire,
@@ -26,11 +29,17 @@ pub(crate) enum WeaponTypes {
 #[derive(Debug, Component)]
 pub(crate) struct DespawnTimer(Timer);
 
+/// Marks a briefly drawn explosion radius.
+#[derive(Debug, Component)]
+pub(crate) struct SplashIndicator {
+    radius: f32,
+}
+
 impl From<WeaponTypes> for EffectType {
     fn from(value: WeaponTypes) -> Self {
         match value {
             WeaponTypes::Laser => EffectType::None,
-            WeaponTypes::Fire => EffectType::None,
+            WeaponTypes::Fire => EffectType::Burn,
             WeaponTypes::Ice => EffectType::Slow,
             WeaponTypes::Rifle => EffectType::None,
         }
@@ -54,15 +63,24 @@ impl WeaponTypes {
         assets.weapon_sprites.extend(sprites.iter().cloned());
     }
 
-    pub(crate) fn range(&self, grid: &GridResource, level_mutator: u32) -> f32 {
-        let base = match self {
+    /// Range in grid squares a new weapon starts with.
+    pub(crate) fn base_range(&self) -> f32 {
+        match self {
             WeaponTypes::Laser => 4.5,
             WeaponTypes::Fire => 2.5,
             WeaponTypes::Ice => 4.0,
             WeaponTypes::Rifle => 10.,
-        };
+        }
+    }
 
-        base * grid.grid_square_size
+    /// Range in grid squares no amount of levels or upgrades can exceed.
+    pub(crate) fn max_range(&self) -> f32 {
+        match self {
+            WeaponTypes::Laser => 7.0,
+            WeaponTypes::Fire => 4.0,
+            WeaponTypes::Ice => 6.0,
+            WeaponTypes::Rifle => 14.0,
+        }
     }
 }
 
@@ -117,38 +135,66 @@ impl From<WeaponTypes> for ProjectileData {
             WeaponTypes::Laser => ProjectileData {
                 count: 1,
                 damage: 3,
+                damage_variance: 0.2,
+                crit_chance: 0.05,
+                crit_multiplier: 1.5,
+                critical: false,
                 weapon_type: WeaponTypes::Laser,
+                effect_type: value.into(),
                 speed_multiplier: 900.0,
                 collision_size: (size.x, size.y),
-                area_of_effect: false,
+                splash_radius: 0.0,
+                splash_falloff: 0.0,
+                pierce: 0,
                 source_entity: None,
             },
             WeaponTypes::Fire => ProjectileData {
                 count: 1,
                 damage: 20,
+                damage_variance: 0.3,
+                crit_chance: 0.0,
+                crit_multiplier: 1.5,
+                critical: false,
                 weapon_type: WeaponTypes::Fire,
+                effect_type: value.into(),
                 speed_multiplier: 100.0,
                 collision_size: (size.x, size.y),
-                area_of_effect: true,
+                splash_radius: 1.5,
+                splash_falloff: 0.5,
+                pierce: 0,
                 source_entity: None,
             },
 
             WeaponTypes::Ice => ProjectileData {
                 count: 4,
                 damage: 1,
+                damage_variance: 0.0,
+                crit_chance: 0.0,
+                crit_multiplier: 1.5,
+                critical: false,
                 weapon_type: WeaponTypes::Ice,
+                effect_type: value.into(),
                 speed_multiplier: 200.0,
                 collision_size: (size.x, size.y),
-                area_of_effect: false,
+                splash_radius: 0.0,
+                splash_falloff: 0.0,
+                pierce: 0,
                 source_entity: None,
             },
             WeaponTypes::Rifle => ProjectileData {
                 count: 1,
                 damage: 30,
+                damage_variance: 0.1,
+                crit_chance: 0.15,
+                crit_multiplier: 2.0,
+                critical: false,
                 weapon_type: WeaponTypes::Rifle,
+                effect_type: value.into(),
                 speed_multiplier: 1600.0,
                 collision_size: (size.x, size.y),
-                area_of_effect: false,
+                splash_radius: 0.0,
+                splash_falloff: 0.0,
+                pierce: 0,
                 source_entity: None,
             },
         }
@@ -172,21 +218,25 @@ impl From<WeaponTypes> for WeaponComponent {
             WeaponTypes::Laser => WeaponComponent {
                 projectile_data: ProjectileData::from(value),
                 reload_timer: Timer::from(value),
+                range: value.base_range(),
                 level: 1,
             },
             WeaponTypes::Fire => WeaponComponent {
                 projectile_data: ProjectileData::from(value),
                 reload_timer: Timer::from(value),
+                range: value.base_range(),
                 level: 1,
             },
             WeaponTypes::Ice => WeaponComponent {
                 projectile_data: ProjectileData::from(value),
                 reload_timer: Timer::from(value),
+                range: value.base_range(),
                 level: 1,
             },
             WeaponTypes::Rifle => WeaponComponent {
                 projectile_data: ProjectileData::from(value),
                 reload_timer: Timer::from(value),
+                range: value.base_range(),
                 level: 1,
             },
         }
@@ -219,6 +269,31 @@ pub(crate) fn despawn_timer_system(
     }
 }
 
+pub(crate) fn splash_system(mut commands: Commands, mut splash_events: EventReader<SplashEvent>) {
+    for splash in splash_events.read() {
+        commands.spawn((
+            SplashIndicator {
+                radius: splash.radius,
+            },
+            Transform::from_translation(splash.position.extend(0.0)),
+            DespawnTimer(Timer::from_seconds(0.25, TimerMode::Once)),
+            StateDespawnMarker(IN_GAME),
+        ));
+    }
+}
+
+pub(crate) fn draw_splash_system(
+    query: Query<(&Transform, &SplashIndicator, &DespawnTimer)>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, splash, timer) in query.iter() {
+        let color = Color::ORANGE_RED.with_a(1.0 - timer.0.fraction());
+        gizmos
+            .circle_2d(transform.translation.truncate(), splash.radius, color)
+            .segments(32);
+    }
+}
+
 pub(crate) fn weapon_fire_system(
     mut commands: Commands,
     assets: Res<SpriteAssets>,
@@ -248,40 +323,117 @@ pub(crate) fn weapon_fire_system(
             colider,
             ExternalForce::ZERO,
             weapon.weapon_projectile_data,
+            ProjectileHits::default(),
             CollisionLayers::new(GameLayer::Projectile, [GameLayer::Enemy]),
             DespawnTimer(Timer::from_seconds(5.0, TimerMode::Once)),
+            StateDespawnMarker(IN_GAME),
         ));
     }
 }
-#[derive(Debug, Clone, Component, Copy, PartialEq)]
+#[derive(Debug, Clone, Component, Copy, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ProjectileData {
     pub count: usize,
     pub damage: usize,
+    /// Fraction the damage roll can deviate from `damage` in either direction.
+    pub damage_variance: f32,
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+    /// Whether the rolled damage of a fired projectile is a critical hit.
+    pub critical: bool,
     pub weapon_type: WeaponTypes,
+    /// The status effect applied on hit.
+    pub effect_type: EffectType,
     pub speed_multiplier: f32,
     pub collision_size: (f32, f32),
-    pub area_of_effect: bool,
+    /// Splash radius in grid squares, zero means no splash.
+    pub splash_radius: f32,
+    /// Fraction of the damage lost at the edge of the splash radius.
+    pub splash_falloff: f32,
+    /// How many enemies the projectile passes through before it is despawned.
+    pub pierce: usize,
+    #[serde(skip)]
     pub source_entity: Option<Entity>,
 }
 
+/// The enemies a projectile has already damaged.
+#[derive(Debug, Clone, Component, Default)]
+pub(crate) struct ProjectileHits(HashSet<Entity>);
+
+impl ProjectileHits {
+    /// Records a hit, returns false if the enemy was already hit.
+    pub(crate) fn insert(&mut self, entity: Entity) -> bool {
+        self.0.insert(entity)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
 impl Display for ProjectileData {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Proj Count: {:?}\nDamage: {}\n Speed: {}\n AoE: {}",
-            self.count, self.damage, self.speed_multiplier, self.area_of_effect
+            "Proj Count: {:?}\nDamage: {}-{}\n Crit: {:.0}% x{:.1}\n Speed: {}\n AoE: {}\n Pierce: {}",
+            self.count,
+            self.damage_range().0,
+            self.damage_range().1,
+            self.crit_chance * 100.0,
+            self.crit_multiplier,
+            self.speed_multiplier,
+            self.splash_radius,
+            self.pierce
         )
     }
 }
 
 impl ProjectileData {
-    pub(crate) fn status_effect(&self, tower: &TowerData) -> StatusEffect {
-        let (potency, duration) = (tower.get_status().potency, tower.get_status().duration);
-        StatusEffect {
-            effect_type: self.weapon_type.into(),
-            timer: Timer::from_seconds(duration as f32, TimerMode::Once),
-            potency: potency,
+    pub(crate) fn damage_range(&self) -> (usize, usize) {
+        let spread = self.damage as f32 * self.damage_variance;
+        (
+            (self.damage as f32 - spread).max(0.0) as usize,
+            (self.damage as f32 + spread) as usize,
+        )
+    }
+
+    /// Damage expected from a roll on average, including crits.
+    pub(crate) fn expected_damage(&self) -> f32 {
+        self.damage as f32 * (1.0 + self.crit_chance * (self.crit_multiplier - 1.0))
+    }
+
+    /// Roll the damage of a fired projectile between its min and max, possibly a crit.
+    pub(crate) fn roll(&mut self, rng: &mut impl Rng) {
+        let (min, max) = self.damage_range();
+        let mut damage = rng.gen_range(min..=max) as f32;
+        self.critical = rng.gen_bool(self.crit_chance.clamp(0.0, 1.0) as f64);
+        if self.critical {
+            damage *= self.crit_multiplier;
         }
+        self.damage = damage as usize;
+    }
+
+    pub(crate) fn area_of_effect(&self) -> bool {
+        self.splash_radius > 0.0
+    }
+
+    pub(crate) fn splash_range(&self, grid: &GridResource) -> f32 {
+        self.splash_radius * grid.grid_square_size
+    }
+
+    /// Damage dealt to an enemy `distance` away from the centre of the splash.
+    pub(crate) fn splash_damage(&self, distance: f32, range: f32) -> usize {
+        let falloff = (distance / range).clamp(0.0, 1.0) * self.splash_falloff;
+        (self.damage as f32 * (1.0 - falloff)) as usize
+    }
+
+    pub(crate) fn status_effect(&self, status: &TowerStatusEffects) -> StatusEffect {
+        let (potency, duration) = (status.potency, status.duration);
+        StatusEffect::new(
+            self.effect_type,
+            self.effect_type.base_duration() + duration as f32,
+            self.effect_type.base_potency() + potency,
+            self.source_entity,
+        )
     }
 }
 
@@ -289,6 +441,8 @@ impl ProjectileData {
 pub(crate) struct WeaponComponent {
     pub projectile_data: ProjectileData,
     pub reload_timer: Timer,
+    /// Range in grid squares before the tower level bonus.
+    pub range: f32,
     pub level: u32,
 }
 
@@ -312,7 +466,7 @@ impl WeaponComponent {
         self.reload_timer.finished()
     }
 
-    pub(crate) fn level_up(&mut self) {
+    pub(crate) fn level_up(&mut self, dmg_rng: &mut impl Rng) {
         let (min, max, flat) = match self.projectile_data.weapon_type {
             WeaponTypes::Laser => (0.01, 0.10, 15),
             WeaponTypes::Fire => (0.01, 0.10, 20),
@@ -320,8 +474,6 @@ impl WeaponComponent {
             WeaponTypes::Rifle => (0.01, 0.23, 45),
         };
 
-        let mut dmg_rng = rand::thread_rng();
-
         let dmg_boost = (self.projectile_data.damage as f32 * dmg_rng.gen_range(min..max))
             + dmg_rng.gen_range(0..flat) as f32;
         self.projectile_data.damage = (self.projectile_data.damage as f32 + dmg_boost) as usize;
@@ -336,19 +488,68 @@ impl WeaponComponent {
         }
 
         if self.level % 10 == 0 {
+            self.add_range(0.25);
             self.reload_timer = Timer::from_seconds(
                 (self.reload_timer.duration().as_secs_f32() * 0.9).max(0.4),
                 TimerMode::Once,
             );
         }
 
+        match self.projectile_data.weapon_type {
+            WeaponTypes::Rifle => {
+                self.projectile_data.crit_chance =
+                    (self.projectile_data.crit_chance + 0.01).min(0.5);
+            }
+            WeaponTypes::Laser if self.level % 5 == 0 => {
+                self.projectile_data.crit_chance =
+                    (self.projectile_data.crit_chance + 0.02).min(0.3);
+                self.projectile_data.crit_multiplier += 0.1;
+            }
+            _ => {}
+        }
+
+        if self.projectile_data.weapon_type == WeaponTypes::Rifle && self.level % 15 == 0 {
+            self.projectile_data.pierce += 1;
+        }
+
         if self.level > 100000 {
-            self.projectile_data.area_of_effect = true;
+            self.projectile_data.splash_radius = self.projectile_data.splash_radius.max(1.0);
         }
 
         self.level += 1;
     }
 
+    pub(crate) fn apply_upgrade(&mut self, effect: &UpgradeEffect) {
+        let data = &mut self.projectile_data;
+        match *effect {
+            UpgradeEffect::Damage(damage) => data.damage += damage,
+            UpgradeEffect::ReloadScale(scale) => {
+                self.reload_timer = Timer::from_seconds(
+                    (self.reload_timer.duration().as_secs_f32() * scale).max(0.2),
+                    TimerMode::Once,
+                );
+            }
+            UpgradeEffect::Pierce(pierce) => data.pierce += pierce,
+            UpgradeEffect::Count(count) => data.count += count,
+            UpgradeEffect::SplashRadius(radius) => data.splash_radius += radius,
+            UpgradeEffect::CritChance(chance) => {
+                data.crit_chance = (data.crit_chance + chance).min(1.0)
+            }
+            UpgradeEffect::OnHit(effect_type) => data.effect_type = effect_type,
+            UpgradeEffect::Range(range) => self.add_range(range),
+        }
+    }
+
+    fn add_range(&mut self, range: f32) {
+        self.range = (self.range + range).min(self.weapon_type().max_range());
+    }
+
+    /// Range in world units, each tower level adds a twentieth of a square.
+    pub(crate) fn range(&self, grid: &GridResource, tower_level: u32) -> f32 {
+        let range = self.range + tower_level as f32 * 0.05;
+        range.min(self.weapon_type().max_range()) * grid.grid_square_size
+    }
+
     pub(crate) fn cost(&self) -> u32 {
         let base = match self.projectile_data.weapon_type {
             WeaponTypes::Laser => 10,
@@ -385,7 +586,7 @@ mod test {
 
     use enum_iterator::all;
 
-    use crate::towers::TowerTypes;
+    use crate::{rng::GameRng, towers::TowerTypes};
 
     use super::*;
 
@@ -395,18 +596,21 @@ mod test {
         grid: &GridResource,
         level: u32,
     ) -> f32 {
-        let range_points = weapon.weapon_type().range(&grid, level) * 3.0;
+        let range_points = weapon.range(&grid, level) * 3.0;
         let count = (weapon.projectile_data.count as f32 / 1.5).max(1.0);
-        let damage_points = weapon.projectile_data.damage as f32;
+        let damage_points = weapon.projectile_data.expected_damage();
         let reload_time_points = 1.0 / (weapon.reload_timer.duration().as_secs_f32() * 2.0);
         let speed_multiplier_points = weapon.projectile_data.speed_multiplier / 300.0;
 
-        let effect_type_points = match EffectType::from(weapon.weapon_type()) {
+        let effect_type_points = match weapon.projectile_data.effect_type {
             EffectType::None => 1.0,
             EffectType::Slow => 2.5,
+            EffectType::Burn | EffectType::Poison => 1.5,
+            EffectType::Stun | EffectType::Freeze => 2.5,
+            EffectType::Vulnerability | EffectType::ArmorShred => 2.0,
             // Add more cases as needed
         };
-        let aoe_scaling = if weapon.projectile_data.area_of_effect {
+        let aoe_scaling = if weapon.projectile_data.area_of_effect() {
             2.0
         } else {
             1.0
@@ -419,18 +623,84 @@ mod test {
         cost_scaling
     }
 
+    #[test]
+    fn test_rifle_gains_pierce() {
+        let mut rng = GameRng::seeded(0);
+        let mut rifle = WeaponComponent::from(WeaponTypes::Rifle);
+        let mut laser = WeaponComponent::from(WeaponTypes::Laser);
+        assert_eq!(rifle.projectile_data.pierce, 0);
+        for _ in 0..30 {
+            rifle.level_up(&mut *rng);
+            laser.level_up(&mut *rng);
+        }
+        assert_eq!(rifle.projectile_data.pierce, 2);
+        assert_eq!(laser.projectile_data.pierce, 0);
+    }
+
+    #[test]
+    fn test_damage_roll_is_seeded() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let template = ProjectileData::from(WeaponTypes::Rifle);
+        let (min, max) = template.damage_range();
+        let roll = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..100)
+                .map(|_| {
+                    let mut shot = template;
+                    shot.roll(&mut rng);
+                    (shot.damage, shot.critical)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let rolls = roll(7);
+        assert_eq!(rolls, roll(7));
+        for (damage, critical) in rolls {
+            let max = if critical {
+                (max as f32 * template.crit_multiplier) as usize
+            } else {
+                max
+            };
+            assert!(damage <= max);
+            assert!(critical || damage >= min);
+        }
+    }
+
+    #[test]
+    fn test_range_is_capped() {
+        let grid = GridResource {
+            grid_square_size: 50.0,
+            ..Default::default()
+        };
+        let mut rng = GameRng::seeded(0);
+        let mut fire = WeaponComponent::from(WeaponTypes::Fire);
+        let base = fire.range(&grid, 0);
+        assert!(fire.range(&grid, 10) > base);
+
+        for _ in 0..200 {
+            fire.level_up(&mut *rng);
+            fire.apply_upgrade(&UpgradeEffect::Range(1.0));
+        }
+        let max = WeaponTypes::Fire.max_range() * grid.grid_square_size;
+        assert_eq!(fire.range(&grid, 1000), max);
+    }
+
     #[test]
     fn test_weapon_balance() {
         let grid = GridResource::default();
         let level = 1;
         let mut points_per_weapon = HashMap::new();
+        let mut rng = GameRng::seeded(0);
 
         for tower in all::<TowerTypes>() {
+            let Some(weapon_type) = tower.weapon() else {
+                continue;
+            };
             let cost = tower.cost(10);
-            let weapon_type: WeaponTypes = tower.into();
             let mut weapon = WeaponComponent::from(weapon_type);
             for _ in 0..10 {
-                weapon.level_up();
+                weapon.level_up(&mut *rng);
             }
 
             let points = calculate_weapon_points(cost, &weapon, &grid, level);